@@ -0,0 +1,244 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::BufRead;
+
+use crate::{Cond, Inst, Operand, Reg};
+
+// One instruction per line, optionally after a "label:".
+// Anything after a ; is a comment. Jumps and branches take a label or an instruction index
+//
+//   loop: add a 1   ; count up
+//         jnz a loop
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnknownInstruction { line: usize, name: String },
+    // Too many or too few operands for the instruction
+    Operands { line: usize, inst: String },
+    BadOperand { line: usize, operand: String },
+    UnknownLabel { line: usize, label: String },
+    DuplicateLabel { line: usize, label: String },
+    // Reading the source failed part way, kept as the message so errors can still be compared
+    Io { line: usize, message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownInstruction { line, name } => write!(f, "line {}: unknown instruction {}", line, name),
+            Error::Operands { line, inst } => write!(f, "line {}: wrong number of operands for {}", line, inst),
+            Error::BadOperand { line, operand } => write!(f, "line {}: bad operand {}", line, operand),
+            Error::UnknownLabel { line, label } => write!(f, "line {}: no label called {}", line, label),
+            Error::DuplicateLabel { line, label } => write!(f, "line {}: {} is already a label", line, label),
+            Error::Io { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+// Splits off the comment and any label, leaving whatever instruction is on the line
+fn split_line(line: &str) -> (Option<&str>, &str) {
+    let code = line.split(';').next().unwrap().trim();
+    match code.split_once(':') {
+        Some((label, rest)) => (Some(label.trim()), rest.trim()),
+        None => (None, code),
+    }
+}
+
+fn reg(line: usize, word: &str) -> Result<Reg, Error> {
+    Reg::ALL.into_iter().find(|r| r.name() == word).ok_or(Error::BadOperand { line, operand: word.to_string() })
+}
+
+fn imm(line: usize, word: &str) -> Result<i32, Error> {
+    word.parse().map_err(|_| Error::BadOperand { line, operand: word.to_string() })
+}
+
+fn operand(line: usize, word: &str) -> Result<Operand, Error> {
+    match reg(line, word) {
+        Ok(r) => Ok(Operand::Reg(r)),
+        Err(_) => imm(line, word).map(Operand::Imm),
+    }
+}
+
+fn target(line: usize, word: &str, labels: &HashMap<&str, usize>) -> Result<usize, Error> {
+    match labels.get(word) {
+        Some(&index) => Ok(index),
+        None => word.parse().map_err(|_| Error::UnknownLabel { line, label: word.to_string() }),
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<Inst>, Error> {
+    // First pass to find out where every label points
+    let mut labels = HashMap::new();
+    let mut index = 0;
+    for (idx, line) in source.lines().enumerate() {
+        let (label, code) = split_line(line);
+        if let Some(label) = label {
+            if labels.insert(label, index).is_some() {
+                return Err(Error::DuplicateLabel { line: idx + 1, label: label.to_string() });
+            }
+        }
+        if !code.is_empty() {
+            index += 1;
+        }
+    }
+
+    let mut program = Vec::new();
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let (_, code) = split_line(line);
+        let words: Vec<&str> = code.split_whitespace().collect();
+        let inst = match words[..] {
+            [] => continue,
+            ["noop"] => Inst::Noop,
+            ["halt"] => Inst::Halt,
+            ["addx", n] => Inst::Addx(imm(line_no, n)?),
+            ["add", r, op] => Inst::Add(reg(line_no, r)?, operand(line_no, op)?),
+            ["sub", r, op] => Inst::Sub(reg(line_no, r)?, operand(line_no, op)?),
+            ["set", r, op] => Inst::Set(reg(line_no, r)?, operand(line_no, op)?),
+            ["load", r, addr] => Inst::Load(reg(line_no, r)?, operand(line_no, addr)?),
+            ["store", r, addr] => Inst::Store(reg(line_no, r)?, operand(line_no, addr)?),
+            ["jmp", to] => Inst::Jmp(target(line_no, to, &labels)?),
+            [branch @ ("jz" | "jnz" | "jn" | "jp"), r, to] => {
+                let cond = match branch {
+                    "jz" => Cond::Zero,
+                    "jnz" => Cond::NonZero,
+                    "jn" => Cond::Negative,
+                    _ => Cond::Positive,
+                };
+                Inst::Branch(cond, reg(line_no, r)?, target(line_no, to, &labels)?)
+            }
+            [name @ ("noop" | "halt" | "addx" | "add" | "sub" | "set" | "load" | "store" | "jmp" | "jz" | "jnz" | "jn" | "jp"), ..] => {
+                return Err(Error::Operands { line: line_no, inst: name.to_string() });
+            }
+            [name, ..] => return Err(Error::UnknownInstruction { line: line_no, name: name.to_string() }),
+        };
+        program.push(inst);
+    }
+    Ok(program)
+}
+
+// Labels can point forwards, so the whole source is read before any of it is assembled
+pub fn read(reader: impl BufRead) -> Result<Vec<Inst>, Error> {
+    let mut source = String::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| Error::Io { line: idx + 1, message: err.to_string() })?;
+        source += &line;
+        source.push('\n');
+    }
+    assemble(&source)
+}
+
+// Source that assembles back to the same program, with a label wherever something jumps to
+pub fn disassemble(program: &[Inst]) -> String {
+    let targets: BTreeSet<usize> = program.iter().filter_map(|inst| match inst {
+        Inst::Jmp(target) | Inst::Branch(_, _, target) if *target <= program.len() => Some(*target),
+        _ => None,
+    }).collect();
+    let name = |target: usize| match targets.contains(&target) {
+        true => format!("l{}", target),
+        false => target.to_string(),
+    };
+
+    let mut out = String::new();
+    for index in 0..=program.len() {
+        if targets.contains(&index) {
+            out += &format!("{}:\n", name(index));
+        }
+        let line = match program.get(index) {
+            Some(Inst::Jmp(target)) => format!("jmp {}", name(*target)),
+            Some(inst @ Inst::Branch(_, r, target)) => format!("{} {} {}", inst.mnemonic(), r.name(), name(*target)),
+            Some(inst) => inst.to_string(),
+            None => break,
+        };
+        out += &format!("    {}\n", line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    // Counts a down from 5, adding each value to x and keeping a running total in memory
+    const COUNTDOWN: &str = "
+        set a 5      ; counter
+    loop:
+        add x a
+        load b 100
+        add b a
+        store b 100
+        sub a 1
+        jp a loop
+    done: halt
+        noop         ; never reached
+    ";
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(COUNTDOWN).unwrap();
+        assert_eq!(program[0], Inst::Set(Reg::A, Operand::Imm(5)));
+        assert_eq!(program[1], Inst::Add(Reg::X, Operand::Reg(Reg::A)));
+        assert_eq!(program[6], Inst::Branch(Cond::Positive, Reg::A, 1));
+        assert_eq!(program[7], Inst::Halt);
+        assert_eq!(program.len(), 9);
+        assert_eq!(assemble("noop\naddx 3\naddx -5").unwrap(), vec![Inst::Noop, Inst::Addx(3), Inst::Addx(-5)]);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut state = State::new(assemble(COUNTDOWN).unwrap());
+        state.run();
+        assert_eq!(state.x, 1 + 5 + 4 + 3 + 2 + 1);
+        assert_eq!(state.memory[&100], 15);
+        assert_eq!(state.reg(Reg::A), 0);
+        // set, then 5 times round 2+2+2+2+2+2, then halt
+        assert_eq!(state.crt.len(), 1 + 5 * 12 + 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("noop\nmul a 2"), Err(Error::UnknownInstruction { line: 2, name: "mul".to_string() }));
+        assert_eq!(assemble("add a"), Err(Error::Operands { line: 1, inst: "add".to_string() }));
+        assert_eq!(assemble("add e 1"), Err(Error::BadOperand { line: 1, operand: "e".to_string() }));
+        assert_eq!(assemble("addx b"), Err(Error::BadOperand { line: 1, operand: "b".to_string() }));
+        assert_eq!(assemble("jmp nowhere"), Err(Error::UnknownLabel { line: 1, label: "nowhere".to_string() }));
+        assert_eq!(assemble("a: noop\na: noop"), Err(Error::DuplicateLabel { line: 2, label: "a".to_string() }));
+        assert_eq!(assemble("jmp nowhere").unwrap_err().to_string(), "line 1: no label called nowhere");
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let source = disassemble(&program);
+        assert!(source.starts_with("    set a 5\nl1:\n    add x a\n"));
+        assert!(source.contains("    jp a l1\n"));
+        assert_eq!(assemble(&source).unwrap(), program);
+        // Jumping to the end is fine, past it stays a number
+        let program = vec![Inst::Jmp(1), Inst::Jmp(7)];
+        assert_eq!(disassemble(&program), "    jmp l1\nl1:\n    jmp 7\n");
+        assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_read() {
+        let owned = String::from("noop\naddx 3\naddx -5\n");
+        let program = read(std::io::BufReader::new(owned.as_bytes())).unwrap();
+        assert_eq!(program, assemble(&owned).unwrap());
+        assert_eq!(read("start:\n  jmp end ; forwards\nend: halt".as_bytes()).unwrap(), vec![Inst::Jmp(1), Inst::Halt]);
+        assert_eq!(read("noop\naddx\n".as_bytes()), Err(Error::Operands { line: 2, inst: "addx".to_string() }));
+        // Not UTF-8
+        assert_eq!(read(&b"noop\n\xff\n"[..]), Err(Error::Io { line: 2, message: "stream did not contain valid UTF-8".to_string() }));
+    }
+
+    #[test]
+    fn test_runtime_program() {
+        // Built up at runtime rather than baked in, and dropped before the state runs
+        let mut state = {
+            let source: String = (1..=3).map(|n| format!("addx {}\n", n)).collect();
+            State::new(assemble(&source).unwrap())
+        };
+        state.run();
+        assert_eq!(state.x, 7);
+    }
+}
@@ -0,0 +1,400 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::{Inst, Reg, State};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Breakpoint {
+    // Stops with the cycle about to run, so registers hold what they will during it
+    Cycle(i32),
+    // Stops once the register changes to the value
+    Reg(Reg, i32),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle {}", cycle),
+            Breakpoint::Reg(r, value) => write!(f, "{} = {}", r.name(), value),
+        }
+    }
+}
+
+// Something to keep an eye on while stepping: registers, "cycle", "mem[n]" and numbers
+// combined with + - * and parentheses
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Num(i32),
+    Reg(Reg),
+    Cycle,
+    Mem(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Option<Expr> {
+        let tokens = tokenize(source)?;
+        let mut pos = 0;
+        let expr = sum(&tokens, &mut pos)?;
+        (pos == tokens.len()).then_some(expr)
+    }
+
+    pub fn eval(&self, state: &State) -> i32 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Reg(r) => state.reg(*r),
+            Expr::Cycle => state.curr_cycle,
+            Expr::Mem(addr) => state.memory.get(&addr.eval(state)).copied().unwrap_or(0),
+            // Wraps the same way the registers do
+            Expr::Add(a, b) => a.eval(state).wrapping_add(b.eval(state)),
+            Expr::Sub(a, b) => a.eval(state).wrapping_sub(b.eval(state)),
+            Expr::Mul(a, b) => a.eval(state).wrapping_mul(b.eval(state)),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Option<Vec<String>> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => {}
+            '+' | '-' | '*' | '(' | ')' | '[' | ']' => tokens.push(c.to_string()),
+            c if c.is_ascii_alphanumeric() => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric()) {
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(word);
+            }
+            _ => return None,
+        }
+    }
+    Some(tokens)
+}
+
+fn sum(tokens: &[String], pos: &mut usize) -> Option<Expr> {
+    let mut expr = product(tokens, pos)?;
+    while let Some(op) = tokens.get(*pos).filter(|t| *t == "+" || *t == "-") {
+        *pos += 1;
+        let rhs = Box::new(product(tokens, pos)?);
+        expr = if op == "+" { Expr::Add(Box::new(expr), rhs) } else { Expr::Sub(Box::new(expr), rhs) };
+    }
+    Some(expr)
+}
+
+fn product(tokens: &[String], pos: &mut usize) -> Option<Expr> {
+    let mut expr = atom(tokens, pos)?;
+    while tokens.get(*pos).is_some_and(|t| t == "*") {
+        *pos += 1;
+        expr = Expr::Mul(Box::new(expr), Box::new(atom(tokens, pos)?));
+    }
+    Some(expr)
+}
+
+fn atom(tokens: &[String], pos: &mut usize) -> Option<Expr> {
+    let token = tokens.get(*pos)?;
+    *pos += 1;
+    let closing = |pos: &mut usize, close: &str| {
+        let closed = tokens.get(*pos).is_some_and(|t| t == close);
+        *pos += 1;
+        closed
+    };
+    match token.as_str() {
+        "cycle" => Some(Expr::Cycle),
+        "-" => Some(Expr::Sub(Box::new(Expr::Num(0)), Box::new(atom(tokens, pos)?))),
+        "(" => {
+            let expr = sum(tokens, pos)?;
+            closing(pos, ")").then_some(expr)
+        }
+        "mem" => {
+            if !closing(pos, "[") {
+                return None;
+            }
+            let addr = sum(tokens, pos)?;
+            closing(pos, "]").then_some(Expr::Mem(Box::new(addr)))
+        }
+        word => match Reg::ALL.into_iter().find(|r| r.name() == word) {
+            Some(r) => Some(Expr::Reg(r)),
+            None => word.parse().ok().map(Expr::Num),
+        },
+    }
+}
+
+// What happened during one cycle
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trace {
+    pub cycle: i32,
+    // The instruction being worked on, whether or not it finished this cycle
+    pub inst: Inst,
+    pub x_before: i32,
+    pub x_after: i32,
+    pub pixel: char,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>5}  {:<16} x {:>3} -> {:<3} {}", self.cycle, self.inst.to_string(), self.x_before, self.x_after, self.pixel)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Stop {
+    // Index into the breakpoints
+    Breakpoint(usize),
+    Finished,
+    // Ran the most cycles a continue is allowed without either of those
+    Limit,
+}
+
+pub struct Debugger {
+    pub state: State,
+    pub breakpoints: Vec<Breakpoint>,
+    pub watches: Vec<(String, Expr)>,
+    pub trace: Vec<Trace>,
+    // Most cycles a single continue runs, so a program that never ends can't hang it
+    pub limit: usize,
+}
+
+impl Debugger {
+    pub fn new(state: State) -> Self {
+        Self { state, breakpoints: vec![], watches: vec![], trace: vec![], limit: 1_000_000 }
+    }
+
+    // Runs a single cycle, None once the program is done
+    pub fn step(&mut self) -> Option<&Trace> {
+        // Leaves the cycle count alone once there's nothing left to run
+        if self.state.finished() {
+            return None;
+        }
+        let x_before = self.state.x;
+        self.state.start();
+        let inst = self.state.next.clone()?;
+        self.state.draw();
+        let pixel = *self.state.crt.last().unwrap();
+        self.state.end();
+        self.trace.push(Trace { cycle: self.state.curr_cycle, inst, x_before, x_after: self.state.x, pixel });
+        self.trace.last()
+    }
+
+    // Runs until a breakpoint, always getting at least one cycle further first
+    pub fn cont(&mut self) -> Stop {
+        for _ in 0..self.limit {
+            let before = Reg::ALL.map(|r| self.state.reg(r));
+            let Some(trace) = self.step() else { return Stop::Finished };
+            let cycle = trace.cycle;
+            let hit = self.breakpoints.iter().position(|bp| match *bp {
+                Breakpoint::Cycle(at) => cycle + 1 == at,
+                Breakpoint::Reg(r, value) => {
+                    let was = before[Reg::ALL.iter().position(|&other| other == r).unwrap()];
+                    was != value && self.state.reg(r) == value
+                }
+            });
+            if let Some(index) = hit {
+                return Stop::Breakpoint(index);
+            }
+        }
+        Stop::Limit
+    }
+
+    pub fn watched(&self) -> Vec<(&str, i32)> {
+        self.watches.iter().map(|(name, expr)| (name.as_str(), expr.eval(&self.state))).collect()
+    }
+}
+
+const HELP: &str = "\
+step [n]            run n cycles, 1 by default
+continue            run until a breakpoint, the end or the cycle limit
+limit <n>           most cycles a continue runs, 1000000 by default
+break <cycle>       stop before a cycle runs
+break <reg> <value> stop when a register changes to a value
+delete <n>          remove breakpoint n
+watch <expr>        show an expression after every stop
+trace [n]           show the last n cycles, all of them by default
+regs                show the registers
+crt                 show the screen so far
+quit";
+
+// A small command line over the debugger, reading commands until quit or the input runs out
+pub fn interactive(debugger: &mut Debugger, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "{}", HELP)?;
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] => continue,
+            ["quit" | "q"] => break,
+            ["step" | "s", ..] => {
+                let n = words.get(1).and_then(|n| n.parse().ok()).unwrap_or(1);
+                for _ in 0..n {
+                    match debugger.step() {
+                        Some(trace) => writeln!(out, "{}", trace)?,
+                        None => {
+                            writeln!(out, "finished")?;
+                            break;
+                        }
+                    }
+                }
+            }
+            ["continue" | "c"] => match debugger.cont() {
+                Stop::Breakpoint(index) => writeln!(out, "breakpoint {}: {}", index, debugger.breakpoints[index])?,
+                Stop::Finished => writeln!(out, "finished")?,
+                Stop::Limit => writeln!(out, "still running after {} cycles", debugger.limit)?,
+            },
+            ["limit", n] => match n.parse() {
+                Ok(n) => debugger.limit = n,
+                Err(_) => writeln!(out, "bad limit {}", n)?,
+            },
+            ["break" | "b", cycle] => match cycle.parse() {
+                Ok(cycle) => debugger.breakpoints.push(Breakpoint::Cycle(cycle)),
+                Err(_) => writeln!(out, "bad cycle {}", cycle)?,
+            },
+            ["break" | "b", r, value] => match (Reg::ALL.into_iter().find(|reg| reg.name() == r), value.parse()) {
+                (Some(r), Ok(value)) => debugger.breakpoints.push(Breakpoint::Reg(r, value)),
+                _ => writeln!(out, "bad breakpoint {} {}", r, value)?,
+            },
+            ["delete" | "d", index] => match index.parse::<usize>() {
+                Ok(index) if index < debugger.breakpoints.len() => {
+                    debugger.breakpoints.remove(index);
+                }
+                _ => writeln!(out, "no breakpoint {}", index)?,
+            },
+            ["watch" | "w", ..] => {
+                let source = words[1..].join(" ");
+                match Expr::parse(&source) {
+                    Some(expr) => debugger.watches.push((source, expr)),
+                    None => writeln!(out, "bad expression {}", source)?,
+                }
+            }
+            ["trace" | "t", ..] => {
+                let n = words.get(1).and_then(|n| n.parse().ok()).unwrap_or(debugger.trace.len());
+                for trace in &debugger.trace[debugger.trace.len().saturating_sub(n)..] {
+                    writeln!(out, "{}", trace)?;
+                }
+            }
+            ["regs" | "r"] => writeln!(out, "{:?}", debugger.state)?,
+            ["crt"] => writeln!(out, "{}", debugger.state)?,
+            _ => writeln!(out, "unknown command {}\n{}", line, HELP)?,
+        }
+        for (name, value) in debugger.watched() {
+            writeln!(out, "{} = {}", name, value)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    const TEST1: &str = "noop\naddx 3\naddx -5";
+    const TEST2: &str = include_str!("test.txt");
+
+    #[test]
+    fn test_trace() {
+        let mut debugger = Debugger::new(State::new(assemble(TEST1).unwrap()));
+        while debugger.step().is_some() {}
+        let trace: Vec<_> = debugger.trace.iter().map(|t| (t.cycle, t.inst.clone(), t.x_before, t.x_after, t.pixel)).collect();
+        assert_eq!(trace, vec![
+            (1, Inst::Noop, 1, 1, '#'),
+            (2, Inst::Addx(3), 1, 1, '#'),
+            (3, Inst::Addx(3), 1, 4, '#'),
+            (4, Inst::Addx(-5), 4, 4, '#'),
+            (5, Inst::Addx(-5), 4, -1, '#'),
+        ]);
+        assert_eq!(debugger.trace[2].to_string(), "    3  addx 3           x   1 -> 4   #");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = Debugger::new(State::new(assemble(TEST2).unwrap()));
+        debugger.breakpoints = vec![Breakpoint::Cycle(20), Breakpoint::Cycle(60)];
+        assert_eq!(debugger.cont(), Stop::Breakpoint(0));
+        // Same as step_to, x is what it is during cycle 20
+        assert_eq!((debugger.state.curr_cycle, debugger.state.x), (19, 21));
+        assert_eq!(debugger.cont(), Stop::Breakpoint(1));
+        assert_eq!(debugger.state.x, 19);
+        assert_eq!(debugger.cont(), Stop::Finished);
+        assert_eq!(debugger.trace.len(), 240);
+
+        let mut debugger = Debugger::new(State::new(assemble(TEST1).unwrap()));
+        debugger.breakpoints = vec![Breakpoint::Reg(Reg::X, 1), Breakpoint::Reg(Reg::X, 4)];
+        // x starts at 1, that doesn't count as changing to it
+        assert_eq!(debugger.cont(), Stop::Breakpoint(1));
+        assert_eq!(debugger.trace.last().unwrap().cycle, 3);
+        assert_eq!(debugger.cont(), Stop::Finished);
+    }
+
+    #[test]
+    fn test_other_registers() {
+        let program = crate::asm::assemble("set a 3\nloop: sub a 1\njnz a loop").unwrap();
+        let mut debugger = Debugger::new(State::new(program));
+        debugger.breakpoints = vec![Breakpoint::Reg(Reg::A, 1)];
+        assert_eq!(debugger.cont(), Stop::Breakpoint(0));
+        // set, then two rounds of sub and jnz, the second sub ending on cycle 7
+        assert_eq!(debugger.state.curr_cycle, 7);
+    }
+
+    #[test]
+    fn test_finished() {
+        // Once it's done nothing more runs, however many times it's asked
+        let mut debugger = Debugger::new(State::new(assemble(TEST1).unwrap()));
+        assert_eq!(debugger.cont(), Stop::Finished);
+        assert_eq!(debugger.state.curr_cycle, 5);
+        assert_eq!(debugger.cont(), Stop::Finished);
+        assert_eq!(debugger.cont(), Stop::Finished);
+        assert!(debugger.step().is_none());
+        assert_eq!((debugger.state.curr_cycle, debugger.trace.len()), (5, 5));
+
+        let mut debugger = Debugger::new(State::new(crate::asm::assemble("set a 1\nhalt\nset a 2").unwrap()));
+        assert_eq!(debugger.cont(), Stop::Finished);
+        assert_eq!(debugger.cont(), Stop::Finished);
+        assert_eq!((debugger.state.reg(Reg::A), debugger.state.curr_cycle), (1, 2));
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut debugger = Debugger::new(State::new(crate::asm::assemble("loop: jmp loop").unwrap()));
+        debugger.limit = 100;
+        assert_eq!(debugger.cont(), Stop::Limit);
+        assert_eq!(debugger.trace.len(), 100);
+        let mut out = Vec::new();
+        interactive(&mut debugger, "limit 10\nc\n".as_bytes(), &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("still running after 10 cycles\n"));
+        assert_eq!(debugger.trace.len(), 110);
+    }
+
+    #[test]
+    fn test_watches() {
+        let mut state = State::new(assemble(TEST2).unwrap());
+        state.step_to(20);
+        state.memory.insert(3, 7);
+        let eval = |source| Expr::parse(source).unwrap().eval(&state);
+        assert_eq!(eval("x * (cycle + 1)"), 420);
+        assert_eq!(eval("x - 1 - 1"), 19);
+        assert_eq!(eval("-x + 2 * 3"), -15);
+        assert_eq!(eval("mem[1 + 2] + mem[4]"), 7);
+        assert_eq!(eval("x * 100000 * 100000"), 21i32.wrapping_mul(100000).wrapping_mul(100000));
+        assert_eq!(Expr::parse("x +"), None);
+        assert_eq!(Expr::parse("(x"), None);
+        assert_eq!(Expr::parse("x y"), None);
+        assert_eq!(Expr::parse("x / 2"), None);
+    }
+
+    #[test]
+    fn test_interactive() {
+        let mut debugger = Debugger::new(State::new(assemble(TEST2).unwrap()));
+        let mut out = Vec::new();
+        interactive(&mut debugger, "b 20\nwatch x * 20\nc\nstep 2\ntrace 1\nnope\nq\nc\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("breakpoint 0: cycle 20\nx * 20 = 420\n"));
+        assert!(out.contains("   20  addx -1          x  21 -> 21  .\n   21  addx -1          x  21 -> 20  #\nx * 20 = 400\n"));
+        assert!(out.contains("unknown command nope"));
+        // Nothing after quit runs
+        assert_eq!(debugger.state.curr_cycle, 21);
+    }
+}
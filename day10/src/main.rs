@@ -1,51 +1,282 @@
+mod asm;
+mod debug;
+mod ocr;
+
+use std::collections::HashMap;
+use std::fmt;
+
+// X is the register the CRT watches, the rest are free for programs to use
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Reg {
+    X,
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Reg {
+    const ALL: [Reg; 5] = [Reg::X, Reg::A, Reg::B, Reg::C, Reg::D];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Reg::X => "x",
+            Reg::A => "a",
+            Reg::B => "b",
+            Reg::C => "c",
+            Reg::D => "d",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Operand {
+    Imm(i32),
+    Reg(Reg),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Imm(n) => write!(f, "{}", n),
+            Operand::Reg(r) => write!(f, "{}", r.name()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Cond {
+    Zero,
+    NonZero,
+    Negative,
+    Positive,
+}
+
+impl Cond {
+    fn holds(&self, value: i32) -> bool {
+        match self {
+            Cond::Zero => value == 0,
+            Cond::NonZero => value != 0,
+            Cond::Negative => value < 0,
+            Cond::Positive => value > 0,
+        }
+    }
+}
+
+// Jump targets are indexes into the program
 #[derive(Debug, PartialEq, Clone)]
 enum Inst {
     Noop,
     Addx(i32),
+    Add(Reg, Operand),
+    Sub(Reg, Operand),
+    Set(Reg, Operand),
+    Jmp(usize),
+    Branch(Cond, Reg, usize),
+    // Memory is addressed by word, anything never stored reads as 0
+    Load(Reg, Operand),
+    Store(Reg, Operand),
+    Halt,
 }
 
 impl Inst {
+    // Like addx, anything doing arithmetic or touching memory takes 2 cycles
     fn cycles(&self) -> i32 {
         match self {
             Inst::Noop => 1,
             Inst::Addx(_) => 2,
+            Inst::Add(..) | Inst::Sub(..) => 2,
+            Inst::Set(..) => 1,
+            Inst::Jmp(_) => 1,
+            Inst::Branch(..) => 2,
+            Inst::Load(..) | Inst::Store(..) => 2,
+            Inst::Halt => 1,
+        }
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Inst::Noop => "noop",
+            Inst::Addx(_) => "addx",
+            Inst::Add(..) => "add",
+            Inst::Sub(..) => "sub",
+            Inst::Set(..) => "set",
+            Inst::Jmp(_) => "jmp",
+            Inst::Branch(Cond::Zero, ..) => "jz",
+            Inst::Branch(Cond::NonZero, ..) => "jnz",
+            Inst::Branch(Cond::Negative, ..) => "jn",
+            Inst::Branch(Cond::Positive, ..) => "jp",
+            Inst::Load(..) => "load",
+            Inst::Store(..) => "store",
+            Inst::Halt => "halt",
+        }
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        match self {
+            Inst::Noop | Inst::Halt => Ok(()),
+            Inst::Addx(n) => write!(f, " {}", n),
+            Inst::Add(r, op) | Inst::Sub(r, op) | Inst::Set(r, op) | Inst::Load(r, op) | Inst::Store(r, op) => {
+                write!(f, " {} {}", r.name(), op)
+            }
+            Inst::Jmp(target) => write!(f, " {}", target),
+            Inst::Branch(_, r, target) => write!(f, " {} {}", r.name(), target),
+        }
+    }
+}
+
+// The screen is drawn left to right, top to bottom, one pixel a cycle, starting over at the top once it's full
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Geometry {
+    width: i32,
+    height: i32,
+    // The sprite is this many pixels wide around x, with any extra pixel going to the right
+    sprite: i32,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Self { width: 40, height: 6, sprite: 3 }
+    }
+}
+
+// Why a screen can't be drawn
+#[derive(Debug, PartialEq)]
+enum GeometryError {
+    Empty { width: i32, height: i32 },
+    NegativeSprite(i32),
+    // More pixels than an i32 can count
+    TooBig { width: i32, height: i32 },
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeometryError::Empty { width, height } => write!(f, "a {}x{} screen has no pixels", width, height),
+            GeometryError::NegativeSprite(sprite) => write!(f, "a sprite can't be {} pixels wide", sprite),
+            GeometryError::TooBig { width, height } => write!(f, "a {}x{} screen has too many pixels", width, height),
+        }
+    }
+}
+
+impl Geometry {
+    fn new(width: i32, height: i32, sprite: i32) -> Result<Geometry, GeometryError> {
+        if width <= 0 || height <= 0 {
+            Err(GeometryError::Empty { width, height })
+        } else if sprite < 0 {
+            Err(GeometryError::NegativeSprite(sprite))
+        } else if width.checked_mul(height).is_none() {
+            Err(GeometryError::TooBig { width, height })
+        } else {
+            Ok(Geometry { width, height, sprite })
         }
     }
+
+    fn lit(&self, x: i32, pos: i32) -> bool {
+        // Widened so x anywhere near the edges of an i32 can't overflow
+        let left = x as i64 - (self.sprite as i64 - 1) / 2;
+        (left..left + self.sprite as i64).contains(&(pos as i64))
+    }
 }
 
-struct State<T: Iterator<Item = Inst>> {
+// Cycles to sample the signal strength at, first and then every so often after that
+fn schedule(first: i32, every: i32, count: usize) -> Vec<i32> {
+    (0..count as i32).map(|n| first + n * every).collect()
+}
+
+struct State {
     x: i32,
+    // a, b, c and d, X lives on its own since everything else about the CRT reads it
+    regs: [i32; 4],
+    memory: HashMap<i32, i32>,
     cycles: i32,
-    program: T,
+    program: Vec<Inst>,
+    // Index of the instruction to fetch next
+    pc: usize,
+    halted: bool,
     next: Option<Inst>,
     curr_cycle: i32,
+    geometry: Geometry,
+    // Every pixel drawn so far, in order
     crt: Vec<char>,
 }
 
-impl<T: Iterator<Item = Inst>> std::fmt::Debug for State<T> {
+impl std::fmt::Debug for State {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "State {{ x: {}, cycles: {}, next: {:?}, curr_cycle: {} }}", self.x, self.cycles, self.next, self.curr_cycle)
+        write!(f, "State {{ x: {}, regs: {:?}, pc: {}, cycles: {}, next: {:?}, curr_cycle: {} }}", self.x, self.regs, self.pc, self.cycles, self.next, self.curr_cycle)
     }
 }
 
-impl<T: Iterator<Item = Inst>> State<T> {
-    fn new(program: T) -> Self {
-        Self { x: 1, cycles: 0, program, next: None, curr_cycle: 0, crt: vec![] }
+impl State {
+    fn new(program: impl IntoIterator<Item = Inst>) -> Self {
+        Self::with_geometry(program, Geometry::default())
+    }
+
+    fn with_geometry(program: impl IntoIterator<Item = Inst>, geometry: Geometry) -> Self {
+        Self {
+            x: 1,
+            regs: [0; 4],
+            memory: HashMap::new(),
+            cycles: 0,
+            program: program.into_iter().collect(),
+            pc: 0,
+            halted: false,
+            next: None,
+            curr_cycle: 0,
+            geometry,
+            crt: vec![],
+        }
+    }
+
+    fn reg(&self, reg: Reg) -> i32 {
+        match reg {
+            Reg::X => self.x,
+            Reg::A => self.regs[0],
+            Reg::B => self.regs[1],
+            Reg::C => self.regs[2],
+            Reg::D => self.regs[3],
+        }
+    }
+
+    fn reg_mut(&mut self, reg: Reg) -> &mut i32 {
+        match reg {
+            Reg::X => &mut self.x,
+            Reg::A => &mut self.regs[0],
+            Reg::B => &mut self.regs[1],
+            Reg::C => &mut self.regs[2],
+            Reg::D => &mut self.regs[3],
+        }
+    }
+
+    fn value(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Imm(n) => n,
+            Operand::Reg(r) => self.reg(r),
+        }
     }
 
     fn start(&mut self) {
-        if let None = self.next {
-            if let Some(inst) = self.program.next() {
+        if self.next.is_none() && !self.halted {
+            if let Some(inst) = self.program.get(self.pc) {
                 self.cycles = inst.cycles()-1;
-                self.next = Some(inst);
+                self.next = Some(inst.clone());
+                self.pc += 1;
             }
         }
         self.curr_cycle += 1;
     }
 
+    // Nothing in progress and nothing left to start
+    fn finished(&self) -> bool {
+        self.next.is_none() && (self.halted || self.pc >= self.program.len())
+    }
+
     fn draw(&mut self) {
-        let pos = (self.curr_cycle - 1) % 40;
-        if pos >= self.x-1 && pos <= self.x+1 {
+        let pos = (self.curr_cycle - 1) % self.geometry.width;
+        if self.geometry.lit(self.x, pos) {
             self.crt.push('#');
         } else {
             self.crt.push('.');
@@ -56,15 +287,38 @@ impl<T: Iterator<Item = Inst>> State<T> {
         if self.cycles == 0 {
             // Time to execute the next instruction
             let inst = self.next.take().unwrap();
-            match inst {
-                Inst::Noop => {},
-                Inst::Addx(arg) => self.x += arg,
-            }
+            self.execute(inst);
         } else {
             self.cycles -= 1;
         }
     }
 
+    fn execute(&mut self, inst: Inst) {
+        match inst {
+            Inst::Noop => {},
+            // Registers wrap around like the real thing
+            Inst::Addx(arg) => self.x = self.x.wrapping_add(arg),
+            Inst::Add(r, op) => *self.reg_mut(r) = self.reg(r).wrapping_add(self.value(op)),
+            Inst::Sub(r, op) => *self.reg_mut(r) = self.reg(r).wrapping_sub(self.value(op)),
+            Inst::Set(r, op) => *self.reg_mut(r) = self.value(op),
+            Inst::Jmp(target) => self.pc = target,
+            Inst::Branch(cond, r, target) => {
+                if cond.holds(self.reg(r)) {
+                    self.pc = target;
+                }
+            }
+            Inst::Load(r, addr) => {
+                let value = self.memory.get(&self.value(addr)).copied().unwrap_or(0);
+                *self.reg_mut(r) = value;
+            }
+            Inst::Store(r, addr) => {
+                let value = self.reg(r);
+                self.memory.insert(self.value(addr), value);
+            }
+            Inst::Halt => self.halted = true,
+        }
+    }
+
     fn step(&mut self) -> bool {
         self.start();
         if self.next.is_none() {
@@ -75,13 +329,14 @@ impl<T: Iterator<Item = Inst>> State<T> {
         true
     }
 
+    // Runs every cycle before the given one, so x is what it is during that cycle
     fn step_to(&mut self, cycle: i32) -> bool {
-        while self.curr_cycle <= cycle-1 {
+        while self.curr_cycle < cycle-1 {
             if !self.step() {
                 panic!("ran out of instructions before reaching cycle {}", cycle);
             }
         }
-        return true;
+        true
     }
 
     fn run(&mut self) {
@@ -89,54 +344,123 @@ impl<T: Iterator<Item = Inst>> State<T> {
     }
 }
 
-impl<T: Iterator<Item = Inst>> ToString for State<T> {
-    fn to_string(&self) -> String {
-        // Lines are 40 characters
-        self.crt.chunks(40).map(|line| {
-            line.iter().collect::<String>()
-        }).collect::<Vec<String>>().join("\n")
+impl State {
+    // What's on the screen right now, row by row. Pixels not drawn yet are dark
+    fn screen(&self) -> Vec<Vec<char>> {
+        let Geometry { width, height, .. } = self.geometry;
+        let mut screen = vec![vec!['.'; width as usize]; height as usize];
+        let size = (width * height) as usize;
+        for (pos, &pixel) in self.crt.iter().enumerate() {
+            let pos = pos % size;
+            screen[pos / width as usize][pos % width as usize] = pixel;
+        }
+        screen
     }
-}
 
-fn parse(input: &'static str) -> impl Iterator<Item = Inst> {
-    input.lines().map(|line| {
-        let mut parts = line.split_whitespace();
-        let inst = parts.next().unwrap();
-        match inst {
-            "noop" => Inst::Noop,
-            "addx" => Inst::Addx(parts.next().unwrap().parse().unwrap()),
-            _ => panic!("unknown instruction"),
+    // The screen as a plain PBM image, where 1 is a lit pixel
+    fn pbm(&self) -> String {
+        let Geometry { width, height, .. } = self.geometry;
+        let mut out = format!("P1\n{} {}\n", width, height);
+        for row in self.screen() {
+            let pixels: Vec<&str> = row.iter().map(|&c| if c == '#' { "1" } else { "0" }).collect();
+            out += &pixels.join(" ");
+            out.push('\n');
         }
-    })
+        out
+    }
 }
 
-fn part1<T: Iterator<Item = Inst>>(program: T) -> i32 {
-    let mut part1 = State::new(program);
-    [20, 60, 100, 140, 180, 220].map(|cycle| {
-        part1.step_to(cycle);
-        part1.x * cycle
-    }).iter().sum()
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines = self.screen().into_iter().map(|line| {
+            line.into_iter().collect::<String>()
+        }).collect::<Vec<String>>();
+        write!(f, "{}", lines.join("\n"))
+    }
 }
 
-fn part2<T: Iterator<Item = Inst>>(program: T) -> String {
+// A program from a file, or stdin for -
+fn load(path: &str) -> Vec<Inst> {
+    let program = match path {
+        "-" => asm::read(std::io::stdin().lock()),
+        path => asm::read(std::io::BufReader::new(std::fs::File::open(path).unwrap())),
+    };
+    program.unwrap_or_else(|err| panic!("{}", err))
+}
+
+// Sum of the signal strengths during each of the cycles, which have to be in order
+fn signal(program: impl IntoIterator<Item = Inst>, cycles: &[i32]) -> i32 {
+    let mut state = State::new(program);
+    cycles.iter().map(|&cycle| {
+        state.step_to(cycle);
+        state.x.wrapping_mul(cycle)
+    }).fold(0, i32::wrapping_add)
+}
+
+fn part1(program: impl IntoIterator<Item = Inst>) -> i32 {
+    signal(program, &schedule(20, 40, 6))
+}
+
+fn part2(program: impl IntoIterator<Item = Inst>) -> String {
     let mut part2 = State::new(program);
     part2.run();
     part2.to_string()
 }
 
 fn main() {
-    println!("Part 1: {}", part1(parse(include_str!("input.txt"))));
-    println!("Part 2:\n{}", part2(parse(include_str!("input.txt"))));
+    let input = asm::assemble(include_str!("input.txt")).unwrap_or_else(|err| panic!("{}", err));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|a| a.as_str()).collect::<Vec<_>>()[..] {
+        ["run", path] => {
+            let mut state = State::new(load(path));
+            state.run();
+            println!("{}", state);
+            println!("{:?}", state);
+        }
+        ["debug", ..] => {
+            let program = args.get(1).map_or(input, |path| load(path));
+            let mut debugger = debug::Debugger::new(State::new(program));
+            debug::interactive(&mut debugger, std::io::stdin().lock(), std::io::stdout()).unwrap();
+        }
+        ["signal", first, every, count] => {
+            let cycles = schedule(first.parse().unwrap(), every.parse().unwrap(), count.parse().unwrap());
+            println!("{}", signal(input, &cycles));
+        }
+        ["crt", width, height, sprite, ..] => {
+            let geometry = Geometry::new(width.parse().unwrap(), height.parse().unwrap(), sprite.parse().unwrap())
+                .unwrap_or_else(|err| panic!("{}", err));
+            let mut state = State::with_geometry(input, geometry);
+            state.run();
+            match args.get(4).map(|a| a.as_str()) {
+                Some("pbm") => print!("{}", state.pbm()),
+                _ => println!("{}", state),
+            }
+        }
+        ["disasm", path] => {
+            print!("{}", asm::disassemble(&load(path)));
+        }
+        ref rest => {
+            // Another puzzle input instead of the bundled one
+            let program = rest.first().map_or(input, |path| load(path));
+            println!("Part 1: {}", part1(program.clone()));
+            let screen = part2(program);
+            let (text, unknown) = ocr::read(&screen);
+            for glyph in unknown {
+                eprintln!("warning: {}", glyph);
+            }
+            println!("Part 2: {}\n{}", text, screen);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    const TEST1: &'static str = "noop\n\
+    const TEST1: &str = "noop\n\
     addx 3\n\
     addx -5";
-    const TEST2: &'static str = include_str!("test.txt");
+    const TEST2: &str = include_str!("test.txt");
 
-    const TEST3: &'static str = "##..##..##..##..##..##..##..##..##..##..\n\
+    const TEST3: &str = "##..##..##..##..##..##..##..##..##..##..\n\
     ###...###...###...###...###...###...###.\n\
     ####....####....####....####....####....\n\
     #####.....#####.....#####.....#####.....\n\
@@ -145,7 +469,7 @@ mod tests {
 
     #[test]
     fn test1() {
-        let mut state = super::State::new(super::parse(TEST1));
+        let mut state = super::State::new(super::asm::assemble(TEST1).unwrap());
         state.step();
         // Cycle 1: noop
         assert_eq!(state.x, 1);
@@ -168,7 +492,7 @@ mod tests {
 
     #[test]
     fn test2() {
-        let mut state = super::State::new(super::parse(TEST2));
+        let mut state = super::State::new(super::asm::assemble(TEST2).unwrap());
         state.step_to(20);
         assert_eq!(state.x, 21);
         state.step_to(60);
@@ -185,14 +509,75 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        assert_eq!(super::part1(super::parse(TEST2)), 13_140);
+        assert_eq!(super::part1(super::asm::assemble(TEST2).unwrap()), 13_140);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Registers wrap, X included even while the CRT is reading it
+        let program = super::asm::assemble("set a 2147483647\nadd a 1\nsub b 2147483647\nsub b 2\nset x 2147483647\naddx 1").unwrap();
+        let mut state = super::State::new(program);
+        state.run();
+        assert_eq!((state.reg(super::Reg::A), state.reg(super::Reg::B), state.x), (i32::MIN, i32::MAX, i32::MIN));
     }
 
     #[test]
     fn test_part2() {
-        let mut state = super::State::new(super::parse(TEST2));
+        let mut state = super::State::new(super::asm::assemble(TEST2).unwrap());
         state.run();
         assert_eq!(state.to_string(), TEST3);
     }
 
+    #[test]
+    fn test_part2_answer() {
+        let screen = super::part2(super::asm::assemble(include_str!("input.txt")).unwrap());
+        assert_eq!(super::ocr::read(&screen), ("PHLHJGZA".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_schedule() {
+        assert_eq!(super::schedule(20, 40, 6), vec![20, 60, 100, 140, 180, 220]);
+        assert_eq!(super::signal(super::asm::assemble(TEST2).unwrap(), &[20, 220]), 420 + 3960);
+        assert_eq!(super::signal(super::asm::assemble(TEST2).unwrap(), &[]), 0);
+    }
+
+    #[test]
+    fn test_sprite() {
+        let lit = |sprite, x| (0..6).filter(|&pos| super::Geometry { sprite, ..Default::default() }.lit(x, pos)).collect::<Vec<_>>();
+        assert_eq!(lit(3, 2), vec![1, 2, 3]);
+        assert_eq!(lit(1, 2), vec![2]);
+        assert_eq!(lit(4, 2), vec![1, 2, 3, 4]);
+        assert_eq!(lit(5, 2), vec![0, 1, 2, 3, 4]);
+        assert_eq!(lit(0, 2), vec![]);
+    }
+
+    #[test]
+    fn test_geometry() {
+        // Same pixels as the example, just wrapped at 20 and wrapping back to the top after 4 rows
+        let geometry = super::Geometry::new(20, 4, 3).unwrap();
+        let mut state = super::State::with_geometry(super::asm::assemble(TEST2).unwrap(), geometry);
+        state.run();
+        let screen = state.to_string();
+        assert_eq!(screen.lines().count(), 4);
+        assert!(screen.lines().all(|line| line.len() == 20));
+        // The last 80 pixels are what's left showing
+        let pixels: String = state.crt[160..].iter().collect();
+        assert_eq!(screen.replace('\n', ""), pixels);
+
+        use super::GeometryError;
+        assert_eq!(super::Geometry::new(0, 6, 3), Err(GeometryError::Empty { width: 0, height: 6 }));
+        assert_eq!(super::Geometry::new(40, -1, 3), Err(GeometryError::Empty { width: 40, height: -1 }));
+        assert_eq!(super::Geometry::new(40, 6, -3), Err(GeometryError::NegativeSprite(-3)));
+        assert_eq!(super::Geometry::new(65536, 65536, 3), Err(GeometryError::TooBig { width: 65536, height: 65536 }));
+        assert_eq!(super::Geometry::new(0, 6, 3).unwrap_err().to_string(), "a 0x6 screen has no pixels");
+    }
+
+    #[test]
+    fn test_pbm() {
+        let mut state = super::State::with_geometry(super::asm::assemble(TEST1).unwrap(), super::Geometry { width: 3, height: 2, sprite: 3 });
+        state.run();
+        // x has moved on to 4 by the second row, and the last pixel is never drawn
+        assert_eq!(state.to_string(), "###\n...");
+        assert_eq!(state.pbm(), "P1\n3 2\n1 1 1\n0 0 0\n");
+    }
 }
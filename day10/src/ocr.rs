@@ -0,0 +1,106 @@
+use std::fmt;
+
+// Letters are 4 pixels wide and 6 tall, with a blank column after each one
+const WIDTH: usize = 4;
+const CELL: usize = WIDTH + 1;
+
+// The letters the puzzle's screen is known to draw, row by row
+const FONT: [(char, &str); 17] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+// A cell that isn't any letter in the font, drawn the way the screen showed it
+#[derive(Debug, PartialEq)]
+pub struct Unknown {
+    pub index: usize,
+    pub glyph: String,
+}
+
+impl fmt::Display for Unknown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "unknown letter {}:", self.index)?;
+        write!(f, "{}", self.glyph)
+    }
+}
+
+// Reads the letters off the screen. Unknown cells come out as ? in the text and are also listed,
+// blank ones as a space
+pub fn read(screen: &str) -> (String, Vec<Unknown>) {
+    let rows: Vec<Vec<char>> = screen.lines().map(|line| line.chars().collect()).collect();
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    for (index, start) in (0..width).step_by(CELL).enumerate() {
+        let cell: Vec<String> = rows.iter().map(|row| {
+            (start..start + WIDTH).map(|x| row.get(x).copied().unwrap_or('.')).collect()
+        }).collect();
+        let pixels = cell.concat();
+        if !pixels.contains('#') {
+            text.push(' ');
+            continue;
+        }
+        match FONT.iter().find(|(_, glyph)| *glyph == pixels) {
+            Some(&(letter, _)) => text.push(letter),
+            None => {
+                text.push('?');
+                unknown.push(Unknown { index, glyph: cell.join("\n") });
+            }
+        }
+    }
+    (text, unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: &str = "\
+###..#..#.#....#..#...##..##..####..##..
+#..#.#..#.#....#..#....#.#..#....#.#..#.
+#..#.####.#....####....#.#......#..#..#.
+###..#..#.#....#..#....#.#.##..#...####.
+#....#..#.#....#..#.#..#.#..#.#....#..#.
+#....#..#.####.#..#..##...###.####.#..#.";
+
+    #[test]
+    fn test_read() {
+        assert_eq!(read(SCREEN), ("PHLHJGZA".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_font() {
+        // Every letter drawn side by side reads back as itself
+        let rows: Vec<String> = (0..6).map(|row| {
+            FONT.iter().map(|(_, glyph)| format!("{}.", &glyph[row * WIDTH..][..WIDTH])).collect()
+        }).collect();
+        let letters: String = FONT.iter().map(|(letter, _)| letter).collect();
+        assert_eq!(read(&rows.join("\n")).0, letters);
+    }
+
+    #[test]
+    fn test_unknown() {
+        let h = ["#..#", "#..#", "####", "#..#", "#..#", "#..#"];
+        let odd = ["#..#", ".##.", ".##.", ".##.", ".##.", "#..#"];
+        let screen: Vec<String> = (0..6).map(|row| format!("{}......{}.", h[row], odd[row])).collect();
+        let (text, unknown) = read(&screen.join("\n"));
+        assert_eq!(text, "H ?");
+        assert_eq!(unknown, vec![Unknown { index: 2, glyph: odd.join("\n") }]);
+        assert_eq!(unknown[0].to_string().lines().next(), Some("unknown letter 2:"));
+    }
+}
@@ -0,0 +1,234 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::expr::Arith;
+
+// Signed integer of any size, for worry levels that are never reduced.
+// Base 2^32 digits, least significant first, with no zero digits on the end so zero is empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Big {
+    neg: bool,
+    mag: Vec<u32>,
+}
+
+fn trim(mut mag: Vec<u32>) -> Vec<u32> {
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    mag
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    out.push(carry as u32);
+    trim(out)
+}
+
+// a has to be at least as big as b
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &digit) in a.iter().enumerate() {
+        let mut diff = digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (diff < 0) as i64;
+        if diff < 0 {
+            diff += 1 << 32;
+        }
+        out.push(diff as u32);
+    }
+    trim(out)
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = out[i + j] as u64 + x as u64 * y as u64 + carry;
+            out[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    trim(out)
+}
+
+fn bit(mag: &[u32], i: usize) -> bool {
+    mag[i / 32] >> (i % 32) & 1 == 1
+}
+
+// Schoolbook long division a bit at a time, slow but only ever used to check things
+fn divrem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem: Vec<u32> = Vec::new();
+    for i in (0..a.len() * 32).rev() {
+        // rem = rem * 2 + the next bit
+        rem = add_mag(&rem, &rem);
+        if bit(a, i) {
+            rem = add_mag(&rem, &[1]);
+        }
+        if cmp_mag(&rem, b) != Ordering::Less {
+            rem = sub_mag(&rem, b);
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+    (trim(quotient), rem)
+}
+
+impl Big {
+    fn new(neg: bool, mag: Vec<u32>) -> Self {
+        let mag = trim(mag);
+        Big { neg: neg && !mag.is_empty(), mag }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.neg
+    }
+
+    pub fn neg(&self) -> Big {
+        Big::new(!self.neg, self.mag.clone())
+    }
+
+    pub fn add(&self, other: &Big) -> Big {
+        if self.neg == other.neg {
+            return Big::new(self.neg, add_mag(&self.mag, &other.mag));
+        }
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => Big::new(other.neg, sub_mag(&other.mag, &self.mag)),
+            _ => Big::new(self.neg, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+
+    pub fn sub(&self, other: &Big) -> Big {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Big) -> Big {
+        Big::new(self.neg != other.neg, mul_mag(&self.mag, &other.mag))
+    }
+
+    // Rounds towards negative infinity for a positive divisor like i128::div_euclid, so the remainder is never negative
+    pub fn div_euclid(&self, other: &Big) -> Big {
+        assert!(!other.is_zero(), "division by zero");
+        let (q, r) = divrem_mag(&self.mag, &other.mag);
+        let q = Big::new(self.neg != other.neg, q);
+        match (self.neg && !r.is_empty(), other.neg) {
+            (true, false) => q.sub(&Big::from(1)),
+            (true, true) => q.add(&Big::from(1)),
+            _ => q,
+        }
+    }
+
+    // Remainder of the size of the number, whatever its sign
+    pub fn rem_u64(&self, m: u64) -> u64 {
+        self.mag.iter().rev().fold(0u128, |r, &digit| ((r << 32) | digit as u128) % m as u128) as u64
+    }
+}
+
+// Never overflows
+impl Arith for Big {
+    fn num(n: u64) -> Self {
+        Big::from(n)
+    }
+    fn is_zero(&self) -> bool {
+        Big::is_zero(self)
+    }
+    fn neg(&self) -> Option<Self> {
+        Some(Big::neg(self))
+    }
+    fn add(&self, other: &Self) -> Option<Self> {
+        Some(Big::add(self, other))
+    }
+    fn sub(&self, other: &Self) -> Option<Self> {
+        Some(Big::sub(self, other))
+    }
+    fn mul(&self, other: &Self) -> Option<Self> {
+        Some(Big::mul(self, other))
+    }
+    fn div(&self, other: &Self) -> Option<Self> {
+        Some(Big::div_euclid(self, other))
+    }
+}
+
+impl From<u64> for Big {
+    fn from(n: u64) -> Big {
+        Big::new(false, vec![n as u32, (n >> 32) as u32])
+    }
+}
+
+impl fmt::Display for Big {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Nine decimal digits at a time
+        let chunk = Big::from(1_000_000_000);
+        let mut chunks = Vec::new();
+        let mut rest = Big::new(false, self.mag.clone());
+        while !rest.is_zero() {
+            chunks.push(rest.rem_u64(1_000_000_000));
+            rest = rest.div_euclid(&chunk);
+        }
+        if self.neg {
+            write!(f, "-")?;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                rest.iter().rev().try_for_each(|chunk| write!(f, "{:09}", chunk))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(n: i128) -> Big {
+        let mag = n.unsigned_abs();
+        Big::new(n < 0, vec![mag as u32, (mag >> 32) as u32, (mag >> 64) as u32, (mag >> 96) as u32])
+    }
+
+    #[test]
+    fn test_matches_i128() {
+        let values = [0, 1, -1, 7, -7, 3, u32::MAX as i128, -(u32::MAX as i128) - 5, u64::MAX as i128, 123_456_789_012_345_678, -98_765_432_109_876];
+        for &a in &values {
+            for &b in &values {
+                assert_eq!(big(a).add(&big(b)), big(a + b), "{} + {}", a, b);
+                assert_eq!(big(a).sub(&big(b)), big(a - b), "{} - {}", a, b);
+                if let Some(product) = a.checked_mul(b) {
+                    assert_eq!(big(a).mul(&big(b)), big(product), "{} * {}", a, b);
+                }
+                if b != 0 {
+                    assert_eq!(big(a).div_euclid(&big(b)), big(a.div_euclid(b)), "{} / {}", a, b);
+                }
+            }
+            assert_eq!(big(a).rem_u64(23), (a.unsigned_abs() % 23) as u64);
+            assert_eq!(big(a).to_string(), a.to_string());
+        }
+    }
+
+    #[test]
+    fn test_huge() {
+        // 2^200 is well past anything built in
+        let two = Big::from(2);
+        let huge = (0..200).fold(Big::from(1), |acc, _| acc.mul(&two));
+        assert_eq!(huge.to_string(), "1606938044258990275541962092341162602522202993782792835301376");
+        assert_eq!(huge.rem_u64(1_000_000_007), 499445072);
+        assert_eq!(huge.div_euclid(&huge.sub(&Big::from(1))), Big::from(1));
+        assert_eq!(Big::from(u64::MAX).to_string(), u64::MAX.to_string());
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use crate::troop::{Item, Relief, Troop};
+
+// Items never affect each other, so each one is followed on its own until it's back somewhere it's been
+// at the start of a round. From then on it goes round the same loop, and the rounds left are whole
+// loops plus part of one
+fn follow(troop: &Troop, relief: &Relief, mut item: Item, rounds: u64) -> Vec<u64> {
+    let monkeys = &troop.monkeys;
+    let mut seen: HashMap<Item, usize> = HashMap::new();
+    // totals[r] is how many times each monkey inspected the item in the first r rounds
+    let mut totals = vec![vec![0; monkeys.len()]];
+    for r in 0.. {
+        if r as u64 == rounds {
+            return totals.pop().unwrap();
+        }
+        if let Some(&start) = seen.get(&item) {
+            let len = (r - start) as u64;
+            let left = rounds - r as u64;
+            let (loops, rest) = (left / len, (left % len) as usize);
+            return (0..monkeys.len()).map(|m| {
+                let per_loop = totals[r][m] - totals[start][m];
+                totals[r][m] + loops * per_loop + totals[start + rest][m] - totals[start][m]
+            }).collect();
+        }
+        seen.insert(item, r);
+        let mut inspected = totals[r].clone();
+        item = troop.item_round(item, relief, &mut inspected);
+        totals.push(inspected);
+    }
+    unreachable!()
+}
+
+// How many items each monkey inspects over any number of rounds with no relief
+pub fn inspected(troop: &Troop, rounds: u64) -> Vec<u64> {
+    let relief = troop.modular();
+    let modulus = troop.modulus();
+    let mut inspected = vec![0; troop.monkeys.len()];
+    for (at, monkey) in troop.monkeys.iter().enumerate() {
+        for &worry in &monkey.items {
+            for (total, n) in inspected.iter_mut().zip(follow(troop, &relief, (at, worry % modulus), rounds)) {
+                *total += n;
+            }
+        }
+    }
+    inspected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    const TEST: &str = include_str!("test.txt");
+
+    #[test]
+    fn test_matches_simulation() {
+        for input in [TEST, include_str!("input.txt")] {
+            for rounds in [0, 1, 20, 1000, 10_000, 12_345] {
+                let mut troop = parse(input);
+                let extrapolated = inspected(&troop, rounds);
+                let relief = troop.modular();
+                troop.run(rounds as usize, &relief);
+                assert_eq!(extrapolated, troop.inspected(), "{} rounds", rounds);
+            }
+        }
+    }
+
+    #[test]
+    fn test_puzzle_counts() {
+        assert_eq!(inspected(&parse(TEST), 10_000), vec![52166, 47830, 1938, 52013]);
+    }
+
+    #[test]
+    fn test_trillion_rounds() {
+        let troop = parse(TEST);
+        let a = inspected(&troop, 1_000_000_000_000);
+        let b = inspected(&troop, 2_000_000_000_000);
+        // Loops are short next to a trillion rounds, so twice the rounds is very nearly twice the count
+        for (a, b) in a.iter().zip(&b) {
+            assert!(b.abs_diff(2 * a) < 1_000_000, "{} vs {}", a, b);
+        }
+        // Every item gets inspected at least once a round
+        assert!(a.iter().sum::<u64>() >= 10 * 1_000_000_000_000);
+    }
+}
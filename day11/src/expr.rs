@@ -0,0 +1,330 @@
+use std::fmt;
+
+// The right hand side of "new = ...", over the old worry level
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Old,
+    Num(u64),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EvalError {
+    Overflow,
+    DivideByZero,
+    Negative,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::Overflow => write!(f, "overflows"),
+            EvalError::DivideByZero => write!(f, "divides by zero"),
+            EvalError::Negative => write!(f, "goes negative"),
+        }
+    }
+}
+
+// Numbers an expression can be worked out in, None meaning it overflowed.
+// Division rounds down like i128::div_euclid and is never by zero
+pub trait Arith: Clone {
+    fn num(n: u64) -> Self;
+    fn is_zero(&self) -> bool;
+    fn neg(&self) -> Option<Self>;
+    fn add(&self, other: &Self) -> Option<Self>;
+    fn sub(&self, other: &Self) -> Option<Self>;
+    fn mul(&self, other: &Self) -> Option<Self>;
+    fn div(&self, other: &Self) -> Option<Self>;
+}
+
+impl Arith for i128 {
+    fn num(n: u64) -> Self {
+        n as i128
+    }
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+    fn neg(&self) -> Option<Self> {
+        self.checked_neg()
+    }
+    fn add(&self, other: &Self) -> Option<Self> {
+        self.checked_add(*other)
+    }
+    fn sub(&self, other: &Self) -> Option<Self> {
+        self.checked_sub(*other)
+    }
+    fn mul(&self, other: &Self) -> Option<Self> {
+        self.checked_mul(*other)
+    }
+    fn div(&self, other: &Self) -> Option<Self> {
+        self.checked_div_euclid(*other)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Old,
+    Num(u64),
+    Op(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            '0'..='9' => {
+                let mut n = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    n.push(d);
+                    chars.next();
+                }
+                tokens.push(Token::Num(n.parse().map_err(|_| format!("{} is too big", n))?));
+            }
+            _ => {
+                let word: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphanumeric())).collect();
+                match word.as_str() {
+                    "old" => tokens.push(Token::Old),
+                    "" => return Err(format!("unexpected {:?}", c)),
+                    _ => return Err(format!("unknown name {}", word)),
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent, loosest binding first:
+//   sum     = product (("+" | "-") product)*
+//   product = unary (("*" | "/") unary)*
+//   unary   = "-" unary | atom
+//   atom    = "old" | number | "(" sum ")"
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        let matched = self.peek() == Some(&Token::Op(op));
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        loop {
+            if self.eat('+') {
+                expr = Expr::Add(Box::new(expr), Box::new(self.product()?));
+            } else if self.eat('-') {
+                expr = Expr::Sub(Box::new(expr), Box::new(self.product()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            if self.eat('*') {
+                expr = Expr::Mul(Box::new(expr), Box::new(self.unary()?));
+            } else if self.eat('/') {
+                expr = Expr::Div(Box::new(expr), Box::new(self.unary()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Old) => Ok(Expr::Old),
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Op('(')) => {
+                let expr = self.sum()?;
+                if !self.eat(')') {
+                    return Err("missing )".to_string());
+                }
+                Ok(expr)
+            }
+            Some(Token::Op(c)) => Err(format!("unexpected {}", c)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Expr, String> {
+        let mut parser = Parser { tokens: tokenize(s)?, pos: 0 };
+        let expr = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {:?}", token)),
+        }
+    }
+
+    // Works the expression out in whatever numbers it's given, stopping at the first overflow
+    pub fn eval_in<T: Arith>(&self, old: &T) -> Result<T, EvalError> {
+        let value = match self {
+            Expr::Old => Some(old.clone()),
+            Expr::Num(n) => Some(T::num(*n)),
+            Expr::Neg(a) => a.eval_in(old)?.neg(),
+            Expr::Add(a, b) => a.eval_in(old)?.add(&b.eval_in(old)?),
+            Expr::Sub(a, b) => a.eval_in(old)?.sub(&b.eval_in(old)?),
+            Expr::Mul(a, b) => a.eval_in(old)?.mul(&b.eval_in(old)?),
+            Expr::Div(a, b) => {
+                let divisor = b.eval_in(old)?;
+                if divisor.is_zero() {
+                    return Err(EvalError::DivideByZero);
+                }
+                a.eval_in(old)?.div(&divisor)
+            }
+        };
+        value.ok_or(EvalError::Overflow)
+    }
+
+    // Worry levels never go negative, but a part of the expression can on the way there,
+    // so everything in between is worked out in i128
+    pub fn checked(&self, old: u64) -> Result<u64, EvalError> {
+        let value = self.eval_in(&(old as i128))?;
+        if value < 0 {
+            return Err(EvalError::Negative);
+        }
+        u64::try_from(value).map_err(|_| EvalError::Overflow)
+    }
+
+    pub fn eval(&self, old: u64) -> u64 {
+        self.checked(old).unwrap_or_else(|err| panic!("new = {} {} for old = {}", self, err, old))
+    }
+
+    // Only + and * keep (a mod m) giving the same answer as a, mod m.
+    // Anything else needs the real worry level
+    pub fn is_modular(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Num(_) => true,
+            Expr::Add(a, b) | Expr::Mul(a, b) => a.is_modular() && b.is_modular(),
+            Expr::Neg(_) | Expr::Sub(..) | Expr::Div(..) => false,
+        }
+    }
+}
+
+// Fully parenthesised, so it always parses back to the same tree
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Num(n) => write!(f, "{}", n),
+            Expr::Neg(a) => write!(f, "-{}", a),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Sub(a, b) => write!(f, "({} - {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Expr::Div(a, b) => write!(f, "({} / {})", a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str, old: u64) -> u64 {
+        Expr::parse(s).unwrap().eval(old)
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("old * 19", 2), 38);
+        assert_eq!(eval("old + 2 * 3", 1), 7);
+        assert_eq!(eval("(old + 2) * 3", 1), 9);
+        assert_eq!(eval("old * old + old", 3), 12);
+        assert_eq!(eval("100 - old - 1", 9), 90);
+        assert_eq!(eval("100 / old / 2", 5), 10);
+        assert_eq!(eval("((old))", 5), 5);
+    }
+
+    #[test]
+    fn test_unary_minus() {
+        assert_eq!(eval("-old + 10", 3), 7);
+        assert_eq!(eval("old * -2 + 20", 3), 14);
+        assert_eq!(eval("--old", 3), 3);
+        assert_eq!(eval("-(3 - old)", 5), 2);
+        assert_eq!(Expr::parse("-old * 2").unwrap(), Expr::parse("(-old) * 2").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "new = (3 - old) goes negative for old = 5")]
+    fn test_negative_result() {
+        eval("3 - old", 5);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Expr::parse("old +"), Err("unexpected end of expression".to_string()));
+        assert_eq!(Expr::parse("(old"), Err("missing )".to_string()));
+        assert_eq!(Expr::parse("old old"), Err("unexpected Old".to_string()));
+        assert_eq!(Expr::parse("new + 1"), Err("unknown name new".to_string()));
+        assert_eq!(Expr::parse("old % 2"), Err("unexpected '%'".to_string()));
+        assert!(Expr::parse("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_checked() {
+        let checked = |s: &str, old| Expr::parse(s).unwrap().checked(old);
+        assert_eq!(checked("old * old", 1 << 31), Ok(1 << 62));
+        assert_eq!(checked("old * old", 1 << 32), Err(EvalError::Overflow));
+        // Too big for u64 on the way, but not by the end
+        assert_eq!(checked("old * 4 / 4", u64::MAX), Ok(u64::MAX));
+        assert_eq!(checked("old * old * old * old", u64::MAX), Err(EvalError::Overflow));
+        assert_eq!(checked("old / (old - 3)", 3), Err(EvalError::DivideByZero));
+        assert_eq!(checked("old - 4", 3), Err(EvalError::Negative));
+    }
+
+    #[test]
+    fn test_display() {
+        let expr = Expr::parse("-old * (2 + old) - 3 / old").unwrap();
+        assert_eq!(expr.to_string(), "((-old * (2 + old)) - (3 / old))");
+        assert_eq!(Expr::parse(&expr.to_string()).unwrap(), expr);
+    }
+
+    #[test]
+    fn test_modular() {
+        let m = 7 * 11 * 13;
+        for s in ["old * old + 3 * (old + 5)", "(old + 1) * (old + 2) * 17"] {
+            let expr = Expr::parse(s).unwrap();
+            assert!(expr.is_modular());
+            for old in [1, 500, 12345, 999_999] {
+                assert_eq!(expr.eval(old % m) % m, expr.eval(old) % m);
+            }
+        }
+        assert!(!Expr::parse("old - 1").unwrap().is_modular());
+        assert!(!Expr::parse("old * -1").unwrap().is_modular());
+        assert!(!Expr::parse("(old + 1) / 2").unwrap().is_modular());
+    }
+}
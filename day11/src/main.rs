@@ -1,73 +1,35 @@
-#![feature(unboxed_closures, fn_traits)]
+mod big;
+mod cycles;
+mod expr;
+mod narrate;
+mod troop;
+mod worry;
+
+use expr::Expr;
 use lazy_regex::regex;
-use std::{cell::RefCell, collections::VecDeque};
-
-#[derive(Debug)]
-enum OpSide {
-    Old,
-    X(u64),
-}
-
-impl OpSide {
-    fn eval(&self, old: u64) -> u64 {
-        match self {
-            OpSide::Old => old as u64,
-            OpSide::X(x) => *x as u64,
-        }
-    }
-}
-
-impl From<&str> for OpSide {
-    fn from(s: &str) -> OpSide {
-        if s == "old" {
-            OpSide::Old
-        } else {
-            OpSide::X(s.parse().unwrap())
-        }
-    }
-}
+use std::collections::VecDeque;
+use troop::{Relief, Troop};
 
 // Operation: new = old * old
 #[derive(Debug)]
 struct Op {
-    left: OpSide,
-    right: OpSide,
-    op: fn(u64, u64) -> u64,
+    expr: Expr,
 }
 
 impl Op {
-    fn eval1(&self, old: u64) -> u64 {
-        (self.op)(self.left.eval(old), self.right.eval(old))/3
-    }
-    fn eval2(&self, old: u64) -> u64 {
-        (self.op)(self.left.eval(old), self.right.eval(old))
+    fn eval(&self, old: u64) -> u64 {
+        self.expr.eval(old)
     }
 }
 
 // "Operation: new = old * old"
 impl From<&str> for Op {
     fn from(s: &str) -> Op {
-        let re = regex!(r"Operation: new = (old|\d+) ([*+-/]) (old|\d+)");
+        let re = regex!(r"Operation: new = (.*)");
         let captures = re.captures(s).unwrap();
-        let mut parts = captures
-            .iter()
-            .skip(1)
-            .map(|x| x.unwrap().as_str());
-
-        let left = parts.next().unwrap().into();
-        let op = parts.next().unwrap();
-        let right = parts.next().unwrap().into();
-        let op = match op {
-            "*" => |x, y| x * y,
-            "+" => |x, y| x + y,
-            "-" => |x, y| x - y,
-            "/" => |x, y| x / y,
-            _ => panic!("Unknown operator {}", op),
-        };
+        let expr = captures.get(1).unwrap().as_str();
         Op {
-            left,
-            right,
-            op,
+            expr: Expr::parse(expr).unwrap_or_else(|err| panic!("Bad operation {}: {}", expr, err)),
         }
     }
 }
@@ -81,7 +43,12 @@ struct Test {
 
 impl Test {
     fn eval(&self, x: u64) -> u64 {
-        if x % self.x == 0 {
+        self.target(x.is_multiple_of(self.x))
+    }
+
+    // Who gets the item, whatever kind of number the worry level is kept as
+    fn target(&self, divisible: bool) -> u64 {
+        if divisible {
             self.if_true
         } else {
             self.if_false
@@ -117,6 +84,16 @@ where T: Iterator<Item = &'a str> {
     }
 }
 
+// One item going through a monkey's hands: worry before, after the operation and after relief,
+// and where it got thrown
+#[derive(Debug, PartialEq)]
+struct Inspection {
+    old: u64,
+    new: u64,
+    relieved: u64,
+    next: usize,
+}
+
 #[derive(Debug)]
 struct Monkey {
     items: VecDeque<u64>,
@@ -135,36 +112,16 @@ impl Monkey {
         }
     }
 
-    fn inspect1(&mut self) -> Option<(usize, u64)> {
-        match self.items.pop_front() {
-            Some(i) => {
-                let i = self.operation.eval1(i);
-                self.inspected += 1;
-                Some((
-                    self.test.eval(i) as usize,
-                    i,
-                ))
-            },
-            None => None,
-        }
-    }
-
-    fn inspect2(&mut self) -> Option<(usize, u64)> {
-        match self.items.pop_front() {
-            Some(i) => {
-                let i = self.operation.eval2(i);
-                self.inspected += 1;
-                Some((
-                    self.test.eval(i) as usize,
-                    i,
-                ))
-            },
-            None => None,
-        }
+    fn inspect(&self, old: u64, relief: &Relief) -> Inspection {
+        let new = self.operation.eval(old);
+        let relieved = relief.apply(new);
+        Inspection { old, new, relieved, next: self.test.eval(relieved) as usize }
     }
 
-    fn add_item(&mut self, item: u64) {
-        self.items.push_back(item);
+    // Where the item goes next and how worried we are about it by then
+    fn throw(&self, worry: u64, relief: &Relief) -> (usize, u64) {
+        let inspection = self.inspect(worry, relief);
+        (inspection.next, inspection.relieved)
     }
 }
 
@@ -187,103 +144,106 @@ impl From<&str> for Monkey {
     }
 }
 
-fn parse(s: &str) -> Vec<RefCell<Monkey>> {
+fn parse(s: &str) -> Troop {
     // Split by 2 newline
-    s.split("\n\n").map(|s| RefCell::new(s.into())).collect()
+    Troop::new(s.split("\n\n").map(|s| s.into()).collect())
 }
 
 fn part1(input: &str) -> u64 {
-    let monkeys = parse(input);
-    let len = monkeys.len();
-    // Run 20 rounds
-    for _ in 0..20 {
-        // 1 round
-        for i in 0..len {
-            let mut monkey = monkeys[i].borrow_mut();
-            while let Some((next, item)) = monkey.inspect1() {
-                monkeys[next].borrow_mut().add_item(item);
-            }
-        }
-    }
-    
-    // Find the top 2 monkeys with most inspected items
-    let mut top = monkeys
-        .iter()
-        .map(|m| m.borrow().inspected).collect::<Vec<_>>();
-    top.sort();
-    // Take the last 2 items
-    top.iter().rev().take(2).product()
+    let mut troop = parse(input);
+    troop.run(20, &Relief::DivideBy3);
+    troop.business(2)
 }
 
 fn part2(input: &str) -> u64 {
-    let monkeys = parse(input);
-    let com_mul: u64 = monkeys.iter().map(|m| m.borrow().test.x).product();
-    let len = monkeys.len();
-    // Run 10,000 rounds
-    for _ in 0..10_000 {
-        // 1 round
-        for i in 0..len {
-            let mut monkey = monkeys[i].borrow_mut();
-            while let Some((next, item)) = monkey.inspect2() {
-                monkeys[next].borrow_mut().add_item(item % com_mul);
-            }
-        }
-    }
-    
-    // Find the top 2 monkeys with most inspected items
-    let mut top = monkeys
-        .iter()
-        .map(|m| m.borrow().inspected).collect::<Vec<_>>();
-    top.sort();
-    // Take the last 2 items
-    top.iter().rev().take(2).product()
+    let mut troop = parse(input);
+    let relief = troop.modular();
+    troop.run(10_000, &relief);
+    troop.business(2)
 }
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1: {}", part1(input));
-    println!("Part 2: {}", part2(input));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|a| a.as_str()).collect::<Vec<_>>()[..] {
+        // Checks a run against the real worry levels, relief is on unless asked for without it
+        [mode @ ("checked" | "exact"), rounds, ..] => {
+            let rounds = rounds.parse().unwrap();
+            let relief = (args.get(2).map(|a| a.as_str()) != Some("norelief")).then_some(&Relief::DivideBy3);
+            let monkeys = parse(input);
+            let inspected = match mode {
+                "checked" => worry::simulate::<u64>(&monkeys, rounds, relief),
+                _ => worry::simulate::<big::Big>(&monkeys, rounds, relief),
+            };
+            match inspected {
+                Ok(inspected) => println!("{:?}", inspected),
+                Err(failure) => println!("{}", failure),
+            }
+        }
+        // Monkey business after any number of rounds, however many that is
+        ["rounds", rounds] => {
+            let mut top = cycles::inspected(&parse(input), rounds.parse().unwrap());
+            top.sort();
+            println!("{}", top.iter().rev().take(2).map(|&n| n as u128).product::<u128>());
+        }
+        // Any number of rounds, with the top n monkeys making up the business and threads if given
+        ["business", rounds, top, relief, ..] => {
+            let mut troop = parse(input);
+            let relief = match relief {
+                "div3" => Relief::DivideBy3,
+                "modular" => troop.modular(),
+                _ => match relief.split_once('=') {
+                    Some(("mod", m)) => Relief::Modulo(m.parse().unwrap()),
+                    Some(("div", d)) => {
+                        let d: u64 = d.parse().unwrap();
+                        Relief::Custom(Box::new(move |worry| worry / d))
+                    }
+                    _ => panic!("Expected div3, modular, mod=<n> or div=<n>"),
+                },
+            };
+            match args.get(4) {
+                Some(threads) => troop.run_parallel(rounds.parse().unwrap(), &relief, threads.parse().unwrap()),
+                None => troop.run(rounds.parse().unwrap(), &relief),
+            }
+            println!("{}", troop.business(top.parse().unwrap()));
+        }
+        // Talks through a run for debugging monkeys, read from file=<path> if given
+        ["log", rounds, ref options @ ..] => {
+            let file = options.iter().find_map(|o| o.strip_prefix("file="));
+            let input = file.map_or_else(|| input.to_string(), |path| std::fs::read_to_string(path).unwrap());
+            let mut troop = parse(&input);
+            let relief = if options.contains(&"modular") { troop.modular() } else { Relief::DivideBy3 };
+            let mut log = narrate::Log::new(&troop, options.contains(&"narrate"), options.contains(&"snapshots"));
+            for _ in 0..rounds.parse().unwrap() {
+                troop.round_observed(&relief, &mut log);
+            }
+            print!("{}", log.text);
+            if options.contains(&"stats") {
+                print!("{}", log.stats());
+            }
+        }
+        _ => {
+            println!("Part 1: {}", part1(input));
+            println!("Part 2: {}", part2(input));
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const TEST: &'static str = 
-"Monkey 0:
-  Starting items: 79, 98
-  Operation: new = old * 19
-  Test: divisible by 23
-    If true: throw to monkey 2
-    If false: throw to monkey 3
-
-Monkey 1:
-  Starting items: 54, 65, 75, 74
-  Operation: new = old + 6
-  Test: divisible by 19
-    If true: throw to monkey 2
-    If false: throw to monkey 0
-
-Monkey 2:
-  Starting items: 79, 60, 97
-  Operation: new = old * old
-  Test: divisible by 13
-    If true: throw to monkey 1
-    If false: throw to monkey 3
-
-Monkey 3:
-  Starting items: 74
-  Operation: new = old + 3
-  Test: divisible by 17
-    If true: throw to monkey 0
-    If false: throw to monkey 1";
+    const TEST: &str = include_str!("test.txt");
 
     #[test]
     fn test_parse_op() {
-        assert_eq!(Op::from("  Operation: new = old * 19").eval1(1), 6);
-        assert_eq!(Op::from("  Operation: new = old + 19").eval1(1), 6);
-        assert_eq!(Op::from("  Operation: new = old * old").eval1(9), 27);
-        assert_eq!(Op::from("  Operation: new = old / 19").eval1(57), 1);
+        let eval1 = |s, old| Relief::DivideBy3.apply(Op::from(s).eval(old));
+        assert_eq!(eval1("  Operation: new = old * 19", 1), 6);
+        assert_eq!(eval1("  Operation: new = old + 19", 1), 6);
+        assert_eq!(eval1("  Operation: new = old * old", 9), 27);
+        assert_eq!(eval1("  Operation: new = old / 19", 57), 1);
+        assert_eq!(eval1("  Operation: new = (old + 1) * -2 + 100", 4), 30);
+        assert_eq!(Op::from("  Operation: new = old * old - old").eval(4), 12);
     }
 
     #[test]
@@ -297,32 +257,26 @@ Monkey 3:
     fn test_parse_monkey() {
         let monkey = Monkey::from("Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n");
         assert_eq!(monkey.items, vec![79, 98]);
-        assert_eq!(monkey.operation.eval1(1), 6);
+        assert_eq!(monkey.throw(1, &Relief::DivideBy3), (3, 6));
         assert_eq!(monkey.test.eval(1), 3);
         assert_eq!(monkey.test.eval(23), 2);
     }
 
     #[test]
     fn test_parse_monkeys() {
-        let monkeys = parse(TEST);
-        assert_eq!(monkeys.len(), 4);
+        let troop = parse(TEST);
+        assert_eq!(troop.monkeys.len(), 4);
     }
 
     #[test]
     fn test_1round() {
-        let monkeys = parse(TEST);
-        let len = monkeys.len();
-        let mult: u64 = monkeys.iter().map(|m| m.borrow().test.x).product();
-        for i in 0..len {
-            let mut monkey = monkeys[i].borrow_mut();
-            while let Some((next, item)) = monkey.inspect1() {
-                monkeys[next].borrow_mut().add_item(item);
-            }
-        }
-        assert_eq!(monkeys[0].borrow().items, vec![20, 23, 27, 26]);
-        assert_eq!(monkeys[1].borrow().items, vec![2080, 25, 167, 207, 401, 1046]);
-        assert_eq!(monkeys[2].borrow().items, vec![]);
-        assert_eq!(monkeys[3].borrow().items, vec![]);
+        let mut troop = parse(TEST);
+        troop.round(&Relief::DivideBy3);
+        let monkeys = &troop.monkeys;
+        assert_eq!(monkeys[0].items, vec![20, 23, 27, 26]);
+        assert_eq!(monkeys[1].items, vec![2080, 25, 167, 207, 401, 1046]);
+        assert_eq!(monkeys[2].items, vec![]);
+        assert_eq!(monkeys[3].items, vec![]);
     }
 
     #[test]
@@ -334,4 +288,17 @@ Monkey 3:
     fn test_part2() {
         assert_eq!(part2(TEST), 2713310158);
     }
+
+    #[test]
+    #[should_panic(expected = "Can't keep worry levels down")]
+    fn test_part2_needs_modular() {
+        part2(&TEST.replace("old + 6", "old - 6"));
+    }
+
+    #[test]
+    fn test_part2_longer_expressions() {
+        // The same operations written out the long way round
+        let long = TEST.replace("old * 19", "old * (10 + 9)").replace("old + 6", "(old + 1) + 5").replace("old + 3", "1 * old + 3");
+        assert_eq!(part2(&long), 2713310158);
+    }
 }
@@ -0,0 +1,186 @@
+use std::fmt::Write;
+
+use crate::expr::Expr;
+use crate::troop::{Observer, Relief, Troop};
+use crate::{Inspection, Monkey};
+
+// Writes up a run the way the puzzle tells it, with any of the step by step narration,
+// the items each monkey holds after every round, and counts of what got thrown where
+pub struct Log {
+    narrate: bool,
+    snapshots: bool,
+    round: usize,
+    // Whose turn the narration is on, so each one gets a heading
+    turn: Option<usize>,
+    pub text: String,
+    // thrown[from][to]
+    pub thrown: Vec<Vec<u64>>,
+}
+
+impl Log {
+    pub fn new(troop: &Troop, narrate: bool, snapshots: bool) -> Self {
+        let len = troop.monkeys.len();
+        Self { narrate, snapshots, round: 0, turn: None, text: String::new(), thrown: vec![vec![0; len]; len] }
+    }
+
+    // A table of items thrown from each monkey down the side to each monkey along the top
+    pub fn stats(&self) -> String {
+        let mut out = format!("Items thrown over {} rounds, from down the side to along the top:\n", self.round);
+        out += &format!("{:>6}", "");
+        for to in 0..self.thrown.len() {
+            out += &format!("{:>8}", to);
+        }
+        out += &format!("{:>8}\n", "total");
+        for (from, row) in self.thrown.iter().enumerate() {
+            out += &format!("{:>6}", from);
+            for &n in row {
+                match n {
+                    0 => out += &format!("{:>8}", "-"),
+                    n => out += &format!("{:>8}", n),
+                }
+            }
+            out += &format!("{:>8}\n", row.iter().sum::<u64>());
+        }
+        out
+    }
+}
+
+// The puzzle's wording for the simple operations, anything fancier gets spelt out
+fn operation(expr: &Expr, new: u64) -> String {
+    match expr {
+        Expr::Mul(a, b) if **a == Expr::Old && **b == Expr::Old => format!("Worry level is multiplied by itself to {}.", new),
+        Expr::Mul(a, b) if **a == Expr::Old => format!("Worry level is multiplied by {} to {}.", b, new),
+        Expr::Add(a, b) if **a == Expr::Old && **b == Expr::Old => format!("Worry level is doubled to {}.", new),
+        Expr::Add(a, b) if **a == Expr::Old => format!("Worry level increases by {} to {}.", b, new),
+        Expr::Sub(a, b) if **a == Expr::Old => format!("Worry level decreases by {} to {}.", b, new),
+        Expr::Div(a, b) if **a == Expr::Old => format!("Worry level is divided by {} to {}.", b, new),
+        expr => format!("Worry level becomes {} to {}.", expr, new),
+    }
+}
+
+impl Observer for Log {
+    fn inspect(&mut self, at: usize, monkey: &Monkey, relief: &Relief, inspection: &Inspection) {
+        let Inspection { old, new, relieved, next } = *inspection;
+        self.thrown[at][next] += 1;
+        if !self.narrate {
+            return;
+        }
+        if self.turn != Some(at) {
+            self.turn = Some(at);
+            writeln!(self.text, "Monkey {}:", at).unwrap();
+        }
+        let relief = match relief {
+            Relief::DivideBy3 => format!("Worry level is divided by 3 to {}.", relieved),
+            Relief::Modulo(m) => format!("Worry level is reduced modulo {} to {}.", m, relieved),
+            Relief::Custom(_) => format!("Worry level is relieved to {}.", relieved),
+        };
+        let not = if relieved.is_multiple_of(monkey.test.x) { "" } else { "not " };
+        writeln!(self.text, "  Monkey inspects an item with a worry level of {}.", old).unwrap();
+        writeln!(self.text, "    {}", operation(&monkey.operation.expr, new)).unwrap();
+        writeln!(self.text, "    Monkey gets bored with item. {}", relief).unwrap();
+        writeln!(self.text, "    Current worry level is {}divisible by {}.", not, monkey.test.x).unwrap();
+        writeln!(self.text, "    Item with worry level {} is thrown to monkey {}.", relieved, next).unwrap();
+    }
+
+    fn round_done(&mut self, troop: &Troop) {
+        self.round += 1;
+        self.turn = None;
+        if !self.snapshots {
+            return;
+        }
+        if self.narrate {
+            self.text.push('\n');
+        }
+        writeln!(self.text, "After round {}, the monkeys are holding items with these worry levels:", self.round).unwrap();
+        for (i, monkey) in troop.monkeys.iter().enumerate() {
+            let items: Vec<String> = monkey.items.iter().map(|item| item.to_string()).collect();
+            writeln!(self.text, "Monkey {}: {}", i, items.join(", ")).unwrap();
+        }
+        self.text.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    const TEST: &str = include_str!("test.txt");
+
+    fn run(rounds: usize, narrate: bool, snapshots: bool) -> Log {
+        let mut troop = parse(TEST);
+        let mut log = Log::new(&troop, narrate, snapshots);
+        for _ in 0..rounds {
+            troop.round_observed(&Relief::DivideBy3, &mut log);
+        }
+        log
+    }
+
+    #[test]
+    fn test_narrate() {
+        let log = run(1, true, false);
+        let expected = "\
+Monkey 0:
+  Monkey inspects an item with a worry level of 79.
+    Worry level is multiplied by 19 to 1501.
+    Monkey gets bored with item. Worry level is divided by 3 to 500.
+    Current worry level is not divisible by 23.
+    Item with worry level 500 is thrown to monkey 3.
+  Monkey inspects an item with a worry level of 98.
+    Worry level is multiplied by 19 to 1862.
+    Monkey gets bored with item. Worry level is divided by 3 to 620.
+    Current worry level is not divisible by 23.
+    Item with worry level 620 is thrown to monkey 3.
+Monkey 1:
+  Monkey inspects an item with a worry level of 54.
+    Worry level increases by 6 to 60.
+    Monkey gets bored with item. Worry level is divided by 3 to 20.
+    Current worry level is not divisible by 19.
+    Item with worry level 20 is thrown to monkey 0.
+";
+        assert!(log.text.starts_with(expected));
+        assert!(log.text.contains(
+            "\
+Monkey 2:
+  Monkey inspects an item with a worry level of 79.
+    Worry level is multiplied by itself to 6241.
+    Monkey gets bored with item. Worry level is divided by 3 to 2080.
+    Current worry level is divisible by 13.
+    Item with worry level 2080 is thrown to monkey 1.
+"
+        ));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let log = run(2, false, true);
+        let expected = "\
+After round 1, the monkeys are holding items with these worry levels:
+Monkey 0: 20, 23, 27, 26
+Monkey 1: 2080, 25, 167, 207, 401, 1046
+Monkey 2: 
+Monkey 3: 
+
+After round 2, the monkeys are holding items with these worry levels:
+Monkey 0: 695, 10, 71, 135, 350
+Monkey 1: 43, 49, 58, 55, 362
+Monkey 2: 
+Monkey 3: 
+
+";
+        assert_eq!(log.text, expected);
+    }
+
+    #[test]
+    fn test_thrown() {
+        let log = run(1, false, false);
+        assert_eq!(log.text, "");
+        assert_eq!(log.thrown, vec![vec![0, 0, 0, 2], vec![4, 0, 0, 0], vec![0, 1, 0, 2], vec![0, 5, 0, 0]]);
+
+        // Everything a monkey inspects gets thrown somewhere
+        let log = run(20, false, false);
+        let totals: Vec<u64> = log.thrown.iter().map(|row| row.iter().sum()).collect();
+        assert_eq!(totals, vec![101, 95, 7, 105]);
+        assert!(log.stats().starts_with("Items thrown over 20 rounds"));
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::thread;
+
+use crate::{Inspection, Monkey};
+
+// What happens to a worry level after a monkey's done with an item and before it decides where it goes
+pub enum Relief {
+    DivideBy3,
+    // Any multiple of every test keeps the same answers, see Troop::modular
+    Modulo(u64),
+    Custom(Box<dyn Fn(u64) -> u64 + Send + Sync>),
+}
+
+impl Relief {
+    pub fn apply(&self, worry: u64) -> u64 {
+        match self {
+            Relief::DivideBy3 => worry / 3,
+            Relief::Modulo(m) => worry % m,
+            Relief::Custom(f) => f(worry),
+        }
+    }
+}
+
+// Every monkey taking its turn once, with the worry levels kept as anything. holding[i] is what monkey i has
+// and throw works out where one of its items goes and what it's worth by then.
+// Anything a monkey throws to itself gets looked at again this round
+pub fn turns<W, E>(
+    holding: &mut [VecDeque<W>],
+    inspected: &mut [u64],
+    mut throw: impl FnMut(usize, W) -> Result<(usize, W), E>,
+) -> Result<(), E> {
+    for i in 0..holding.len() {
+        while let Some(worry) = holding[i].pop_front() {
+            let (next, worry) = throw(i, worry)?;
+            inspected[i] += 1;
+            holding[next].push_back(worry);
+        }
+    }
+    Ok(())
+}
+
+// Gets told everything that happens during a round
+pub trait Observer {
+    fn inspect(&mut self, _at: usize, _monkey: &Monkey, _relief: &Relief, _inspection: &Inspection) {}
+    fn round_done(&mut self, _troop: &Troop) {}
+}
+
+// Not watching
+impl Observer for () {}
+
+// Which monkey has an item and how worried we are about it
+pub type Item = (usize, u64);
+
+pub struct Troop {
+    pub monkeys: Vec<Monkey>,
+}
+
+impl Troop {
+    pub fn new(monkeys: Vec<Monkey>) -> Self {
+        Self { monkeys }
+    }
+
+    // Relief that keeps worry levels down modulo every test at once.
+    // Only works when every operation is just + and *
+    pub fn modular(&self) -> Relief {
+        if let Some(monkey) = self.monkeys.iter().find(|m| !m.operation.expr.is_modular()) {
+            panic!("Can't keep worry levels down modulo the tests with new = {}", monkey.operation.expr);
+        }
+        Relief::Modulo(self.modulus())
+    }
+
+    // Product of every test
+    pub fn modulus(&self) -> u64 {
+        self.monkeys.iter().map(|m| m.test.x).product()
+    }
+
+    pub fn round(&mut self, relief: &Relief) {
+        self.round_observed(relief, &mut ());
+    }
+
+    pub fn round_observed(&mut self, relief: &Relief, observer: &mut impl Observer) {
+        let mut holding: Vec<_> = self.monkeys.iter_mut().map(|m| std::mem::take(&mut m.items)).collect();
+        let mut inspected = vec![0; self.monkeys.len()];
+        let monkeys = &self.monkeys;
+        let Ok(()) = turns(&mut holding, &mut inspected, |i, worry| {
+            let inspection = monkeys[i].inspect(worry, relief);
+            observer.inspect(i, &monkeys[i], relief, &inspection);
+            Ok::<_, Infallible>((inspection.next, inspection.relieved))
+        });
+        for ((monkey, items), n) in self.monkeys.iter_mut().zip(holding).zip(inspected) {
+            monkey.items = items;
+            monkey.inspected += n;
+        }
+        observer.round_done(self);
+    }
+
+    pub fn run(&mut self, rounds: usize, relief: &Relief) {
+        for _ in 0..rounds {
+            self.round(relief);
+        }
+    }
+
+    // Everything that happens to one item in a round, counting inspections as it goes.
+    // Thrown to the same monkey or one further down the list it gets looked at again this round,
+    // otherwise it waits for the next
+    pub fn item_round(&self, (mut at, mut worry): Item, relief: &Relief, inspected: &mut [u64]) -> Item {
+        loop {
+            let (next, new) = self.monkeys[at].throw(worry, relief);
+            inspected[at] += 1;
+            worry = new;
+            if next < at {
+                return (next, worry);
+            }
+            at = next;
+        }
+    }
+
+    // Same counts as run, but with the items shared out between threads since they never affect each other.
+    // Every item ends up with the right monkey, though not necessarily in the same order
+    pub fn run_parallel(&mut self, rounds: usize, relief: &Relief, threads: usize) {
+        let items: Vec<Item> = self.monkeys.iter_mut().enumerate().flat_map(|(at, monkey)| {
+            monkey.items.drain(..).map(move |worry| (at, worry)).collect::<Vec<_>>()
+        }).collect();
+        let chunk = items.len().div_ceil(threads.max(1)).max(1);
+        let troop = &*self;
+        let results: Vec<(Vec<Item>, Vec<u64>)> = thread::scope(|scope| {
+            let handles: Vec<_> = items.chunks(chunk).map(|items| scope.spawn(move || {
+                let mut inspected = vec![0; troop.monkeys.len()];
+                let items = items.iter().map(|&item| {
+                    (0..rounds).fold(item, |item, _| troop.item_round(item, relief, &mut inspected))
+                }).collect();
+                (items, inspected)
+            })).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        for (items, inspected) in results {
+            for (at, worry) in items {
+                self.monkeys[at].items.push_back(worry);
+            }
+            for (monkey, n) in self.monkeys.iter_mut().zip(inspected) {
+                monkey.inspected += n;
+            }
+        }
+    }
+
+    pub fn inspected(&self) -> Vec<u64> {
+        self.monkeys.iter().map(|m| m.inspected).collect()
+    }
+
+    // The product of the top n monkeys' inspection counts
+    pub fn business(&self, n: usize) -> u64 {
+        let mut top = self.inspected();
+        top.sort();
+        top.iter().rev().take(n).product()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    const TEST: &str = include_str!("test.txt");
+
+    #[test]
+    fn test_relief() {
+        let mut troop = parse(TEST);
+        troop.run(20, &Relief::DivideBy3);
+        assert_eq!(troop.inspected(), vec![101, 95, 7, 105]);
+        assert_eq!(troop.business(2), 10605);
+        assert_eq!(troop.business(1), 105);
+        assert_eq!(troop.business(4), 101 * 95 * 7 * 105);
+
+        // Any multiple of the tests works as well as their product
+        let mut troop = parse(TEST);
+        troop.run(1000, &Relief::Modulo(96577 * 2));
+        assert_eq!(troop.inspected(), vec![5204, 4792, 199, 5192]);
+        let mut troop = parse(TEST);
+        troop.run(1000, &Relief::Custom(Box::new(|worry| worry % 96577)));
+        assert_eq!(troop.inspected(), vec![5204, 4792, 199, 5192]);
+    }
+
+    #[test]
+    fn test_rounds() {
+        let mut troop = parse(TEST);
+        let relief = troop.modular();
+        troop.run(10_000, &relief);
+        assert_eq!(troop.business(2), 2713310158);
+    }
+
+    #[test]
+    fn test_parallel() {
+        for threads in [1, 2, 3, 16] {
+            let mut troop = parse(TEST);
+            troop.run_parallel(20, &Relief::DivideBy3, threads);
+            assert_eq!(troop.inspected(), vec![101, 95, 7, 105], "{} threads", threads);
+            let mut sequential = parse(TEST);
+            sequential.run(20, &Relief::DivideBy3);
+            for (a, b) in troop.monkeys.iter().zip(&sequential.monkeys) {
+                let mut a: Vec<_> = a.items.iter().collect();
+                let mut b: Vec<_> = b.items.iter().collect();
+                a.sort();
+                b.sort();
+                assert_eq!(a, b);
+            }
+        }
+        let mut troop = parse(include_str!("input.txt"));
+        let relief = troop.modular();
+        troop.run_parallel(10_000, &relief, 4);
+        assert_eq!(troop.business(2), 13237873355);
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use crate::big::Big;
+use crate::expr::{EvalError, Expr};
+use crate::troop::{self, Relief, Troop};
+use std::collections::VecDeque;
+
+// A worry level that's never reduced modulo anything, so tests see the real thing
+pub trait Worry: Clone + fmt::Display {
+    fn new(n: u64) -> Self;
+    fn apply(&self, expr: &Expr) -> Result<Self, EvalError>;
+    fn relieve(&self, relief: &Relief) -> Self;
+    fn divisible_by(&self, m: u64) -> bool;
+}
+
+// Fails as soon as anything doesn't fit
+impl Worry for u64 {
+    fn new(n: u64) -> Self {
+        n
+    }
+    fn apply(&self, expr: &Expr) -> Result<Self, EvalError> {
+        expr.checked(*self)
+    }
+    fn relieve(&self, relief: &Relief) -> Self {
+        relief.apply(*self)
+    }
+    fn divisible_by(&self, m: u64) -> bool {
+        self.is_multiple_of(m)
+    }
+}
+
+// Never overflows, but gets slow once the numbers are thousands of digits long
+impl Worry for Big {
+    fn new(n: u64) -> Self {
+        Big::from(n)
+    }
+    fn apply(&self, expr: &Expr) -> Result<Self, EvalError> {
+        match expr.eval_in(self)? {
+            value if value.is_negative() => Err(EvalError::Negative),
+            value => Ok(value),
+        }
+    }
+    fn relieve(&self, relief: &Relief) -> Self {
+        match relief {
+            Relief::DivideBy3 => self.div_euclid(&Big::from(3)),
+            Relief::Modulo(m) => Big::from(self.rem_u64(*m)),
+            Relief::Custom(_) => panic!("Custom relief only works on u64 worry levels"),
+        }
+    }
+    fn divisible_by(&self, m: u64) -> bool {
+        self.rem_u64(m) == 0
+    }
+}
+
+// Where a run had to stop, rounds counting from 1
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub round: usize,
+    pub monkey: usize,
+    pub old: String,
+    pub error: EvalError,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "round {}, monkey {}: the operation {} for an item with a worry level of {}", self.round, self.monkey, self.error, self.old)
+    }
+}
+
+// How many items each monkey inspects over the rounds, with worry levels kept as W instead of the troop's own.
+// Without any relief they're left exactly as the operations make them
+pub fn simulate<W: Worry>(troop: &Troop, rounds: usize, relief: Option<&Relief>) -> Result<Vec<u64>, Failure> {
+    let monkeys = &troop.monkeys;
+    let mut holding: Vec<VecDeque<W>> = monkeys.iter().map(|m| m.items.iter().map(|&i| W::new(i)).collect()).collect();
+    let mut inspected = vec![0; monkeys.len()];
+    for round in 1..=rounds {
+        troop::turns(&mut holding, &mut inspected, |i, old| {
+            let monkey = &monkeys[i];
+            let new = old.apply(&monkey.operation.expr).map_err(|error| Failure { round, monkey: i, old: old.to_string(), error })?;
+            let new = match relief {
+                Some(relief) => new.relieve(relief),
+                None => new,
+            };
+            Ok((monkey.test.target(new.divisible_by(monkey.test.x)) as usize, new))
+        })?;
+    }
+    Ok(inspected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    const TEST: &str = include_str!("test.txt");
+
+    #[test]
+    fn test_checked_part1() {
+        assert_eq!(simulate::<u64>(&parse(TEST), 20, Some(&Relief::DivideBy3)), Ok(vec![101, 95, 7, 105]));
+        assert_eq!(simulate::<u64>(&parse(include_str!("input.txt")), 20, Some(&Relief::DivideBy3)).map(|_| ()), Ok(()));
+    }
+
+    #[test]
+    fn test_checked_overflow() {
+        // Monkey 2 squares whatever it gets, which soon stops fitting without the relief
+        let failure = simulate::<u64>(&parse(TEST), 20, None).unwrap_err();
+        assert_eq!(failure.error, EvalError::Overflow);
+        assert!(failure.round < 20);
+        assert!(failure.old.parse::<u64>().unwrap() > u32::MAX as u64);
+        assert!(failure.to_string().starts_with(&format!("round {}, monkey {}: the operation overflows", failure.round, failure.monkey)));
+    }
+
+    #[test]
+    fn test_exact_matches_modular() {
+        let monkeys = parse(TEST);
+        for rounds in [1, 5, 10, 14] {
+            let exact = simulate::<Big>(&monkeys, rounds, None).unwrap();
+            let mut troop = parse(TEST);
+            let relief = troop.modular();
+            troop.run(rounds, &relief);
+            assert_eq!(exact, troop.inspected(), "{} rounds", rounds);
+        }
+        // Any relief the troop uses works the same on the exact levels
+        let mut troop = parse(TEST);
+        let relief = troop.modular();
+        troop.run(1000, &relief);
+        assert_eq!(simulate::<Big>(&monkeys, 1000, Some(&relief)), Ok(troop.inspected()));
+        // Relief and the exact levels agree with the checked ones where they fit
+        assert_eq!(simulate::<Big>(&monkeys, 20, Some(&Relief::DivideBy3)), simulate::<u64>(&monkeys, 20, Some(&Relief::DivideBy3)));
+    }
+
+    #[test]
+    fn test_exact_negative() {
+        let monkeys = parse(&TEST.replace("old + 6", "old - 100"));
+        let failure = simulate::<Big>(&monkeys, 1, Some(&Relief::DivideBy3)).unwrap_err();
+        assert_eq!(failure, Failure { round: 1, monkey: 1, old: "54".to_string(), error: EvalError::Negative });
+    }
+}
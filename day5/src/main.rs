@@ -1,5 +1,9 @@
 use itertools::Itertools;
 use regex::Regex;
+use std::fmt;
+
+// Crate labels can be more than one character wide, e.g. [AB]
+type Crate = String;
 
 #[derive(Debug, PartialEq)]
 struct Instruction {
@@ -20,22 +24,297 @@ impl From<&str> for Instruction {
     }
 }
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.n, self.from, self.to)
+    }
+}
+
 impl Instruction {
-    fn apply(&self, stacks: &mut Vec<Vec<char>>) {
+    fn apply(&self, stacks: &mut [Vec<Crate>]) {
         let len = stacks[self.from - 1].len();
         let mut stack = stacks[self.from - 1].drain((len - self.n)..).collect::<Vec<_>>();
         stack.reverse();
         stacks[self.to - 1].append(&mut stack);
     }
 
-    fn apply9001(&self, stacks: &mut Vec<Vec<char>>) {
+    fn apply9001(&self, stacks: &mut [Vec<Crate>]) {
         let len = stacks[self.from - 1].len();
         let mut stack = stacks[self.from - 1].drain((len - self.n)..).collect::<Vec<_>>();
         stacks[self.to - 1].append(&mut stack);
     }
 }
 
-fn parse_stacks(input: &str) -> Vec<Vec<char>> {
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Crane {
+    CrateMover9000,
+    CrateMover9001,
+}
+
+impl Crane {
+    fn apply(&self, inst: &Instruction, stacks: &mut [Vec<Crate>]) {
+        match self {
+            Crane::CrateMover9000 => inst.apply(stacks),
+            Crane::CrateMover9001 => inst.apply9001(stacks),
+        }
+    }
+
+    // The top n crates of a stack in the order they land on the destination
+    fn lifted(&self, stack: &[Crate], n: usize) -> Vec<Crate> {
+        let mut crates = stack[stack.len() - n..].to_vec();
+        if *self == Crane::CrateMover9000 {
+            crates.reverse();
+        }
+        crates
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Goal {
+    // Every stack must match exactly, bottom to top
+    Arrangement(Vec<Vec<Crate>>),
+    // Only the top crate of each stack matters, None means don't care
+    Tops(Vec<Option<Crate>>),
+}
+
+impl Goal {
+    // Labels separated by commas so crates like [AB] fit, an empty label means don't care.
+    // Without a comma it's one single character crate per stack, like top_of_stack gives
+    // for the puzzle's crates, with a space for don't care
+    fn tops(s: &str) -> Goal {
+        let label = |label: &str| if label.trim().is_empty() { None } else { Some(label.trim().to_string()) };
+        if s.contains(',') {
+            Goal::Tops(s.split(',').map(label).collect())
+        } else {
+            Goal::Tops(s.chars().map(|c| label(&c.to_string())).collect())
+        }
+    }
+
+    fn is_met(&self, stacks: &[Vec<Crate>]) -> bool {
+        match self {
+            Goal::Arrangement(goal) => goal == stacks,
+            Goal::Tops(tops) => {
+                tops.len() == stacks.len()
+                    && tops.iter().zip(stacks).all(|(top, stack)| top.is_none() || top.as_ref() == stack.last())
+            }
+        }
+    }
+}
+
+// Tracks the stacks as moves are emitted so every decision sees the real state
+struct Planner {
+    stacks: Vec<Vec<Crate>>,
+    crane: Crane,
+    moves: Vec<Instruction>,
+}
+
+impl Planner {
+    fn new(stacks: &[Vec<Crate>], crane: Crane) -> Self {
+        Self { stacks: stacks.to_vec(), crane, moves: Vec::new() }
+    }
+
+    // Stacks are 0-indexed here, instructions are 1-indexed like the puzzle
+    fn push(&mut self, n: usize, from: usize, to: usize) {
+        if n == 0 {
+            return;
+        }
+        let inst = Instruction { n, from: from + 1, to: to + 1 };
+        self.crane.apply(&inst, &mut self.stacks);
+        self.moves.push(inst);
+    }
+
+    fn arrangement(&mut self, goal: &[Vec<Crate>]) -> Option<()> {
+        let len = self.stacks.len();
+        if goal.len() != len || !same_crates(&self.stacks, goal) {
+            return None;
+        }
+        // The bottom of each stack that already matches the goal never has to move,
+        // we only ever put crates on top of it
+        let mut fixed: Vec<usize> = self.stacks.iter().zip(goal)
+            .map(|(stack, want)| stack.iter().zip(want).take_while(|(a, b)| a == b).count())
+            .collect();
+
+        loop {
+            let pending: Vec<usize> = (0..len).filter(|&t| fixed[t] < goal[t].len()).collect();
+            // Every crate is in its final spot, so there can't be anything left over either
+            let Some(&t) = pending.first() else {
+                return Some(());
+            };
+
+            // Best case: the next crates for some clean stack are sitting on top of another one
+            let mut best: Option<(usize, usize, usize)> = None;
+            for &to in pending.iter().filter(|&&to| self.stacks[to].len() == fixed[to]) {
+                let want = &goal[to][fixed[to]..];
+                for from in (0..len).filter(|&from| from != to) {
+                    let loose = self.stacks[from].len() - fixed[from];
+                    let n = (1..=loose.min(want.len()))
+                        .rev()
+                        .find(|&n| self.crane.lifted(&self.stacks[from], n) == want[..n]);
+                    if let Some(n) = n {
+                        if best.is_none_or(|(b, _, _)| n > b) {
+                            best = Some((n, from, to));
+                        }
+                    }
+                }
+            }
+            if let Some((n, from, to)) = best {
+                self.push(n, from, to);
+                fixed[to] += n;
+                continue;
+            }
+
+            // Get anything that doesn't belong off the target
+            let junk = self.stacks[t].len() - fixed[t];
+            if junk > 0 {
+                let dump = (0..len).find(|&d| d != t)?;
+                self.push(junk, t, dump);
+                continue;
+            }
+
+            // Dig out the shallowest crate we need next, the next pass places it
+            let c = &goal[t][fixed[t]];
+            let (from, depth) = (0..len)
+                .filter(|&s| s != t)
+                .filter_map(|s| {
+                    let stack = &self.stacks[s];
+                    stack[fixed[s]..].iter().rev().position(|x| x == c).map(|depth| (s, depth))
+                })
+                .min_by_key(|&(_, depth)| depth)?;
+            let dump = (0..len).find(|&d| d != from && d != t)?;
+            self.push(depth, from, dump);
+        }
+    }
+
+    // Fixes the tops one stack at a time in the given order
+    fn tops(&mut self, tops: &[Option<Crate>], order: &[usize]) -> Option<()> {
+        let len = self.stacks.len();
+        if tops.len() != len {
+            return None;
+        }
+        // Stacks whose top is already right are left alone for the rest of the plan,
+        // anything else (including stacks we don't care about) is fair game to dig in or dump on
+        let mut done: Vec<bool> = (0..len)
+            .map(|i| tops[i].is_some() && tops[i].as_ref() == self.stacks[i].last())
+            .collect();
+
+        for &t in order {
+            if done[t] {
+                continue;
+            }
+            let c = tops[t].as_ref().unwrap();
+            // Cheapest way to bring any reachable copy of c to the top of t
+            let mut best: Option<Vec<(usize, usize, usize)>> = None;
+            for s in (0..len).filter(|&s| !done[s] || s == t) {
+                let stack = &self.stacks[s];
+                for depth in stack.iter().rev().positions(|x| x == c) {
+                    let plan = if s == t && depth == 0 {
+                        vec![]
+                    } else if s == t {
+                        // Buried in the target itself, the crates above have to go somewhere harmless
+                        match (0..len).find(|&d| d != t && !done[d]) {
+                            Some(dump) => vec![(depth, t, dump)],
+                            None => continue,
+                        }
+                    } else if self.crane.lifted(stack, depth + 1).last() == Some(c) {
+                        vec![(depth + 1, s, t)]
+                    } else {
+                        // Whatever is above c can go underneath it on the target
+                        vec![(depth, s, t), (1, s, t)]
+                    };
+                    let cost = |plan: &Vec<(usize, usize, usize)>| plan.iter().filter(|(n, _, _)| *n > 0).count();
+                    if best.as_ref().is_none_or(|b| cost(&plan) < cost(b)) {
+                        best = Some(plan);
+                    }
+                }
+            }
+            for (n, from, to) in best? {
+                self.push(n, from, to);
+            }
+            done[t] = true;
+        }
+        Some(())
+    }
+}
+
+fn same_crates(a: &[Vec<Crate>], b: &[Vec<Crate>]) -> bool {
+    let mut a = a.concat();
+    let mut b = b.concat();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+// Pull each wanted crate out from wherever it's closest to the top and put it on its stack,
+// everything else stays where it is
+fn arrangement_for(stacks: &[Vec<Crate>], tops: &[Option<Crate>]) -> Option<Vec<Vec<Crate>>> {
+    let mut want = stacks.to_vec();
+    for c in tops.iter().flatten() {
+        let (s, idx) = want
+            .iter()
+            .enumerate()
+            .filter_map(|(s, stack)| stack.iter().rposition(|x| x == c).map(|idx| (s, idx)))
+            .min_by_key(|&(s, idx)| want[s].len() - idx)?;
+        want[s].remove(idx);
+    }
+    for (stack, top) in want.iter_mut().zip(tops) {
+        stack.extend(top.iter().cloned());
+    }
+    Some(want)
+}
+
+// Replays the moves from the start and checks they end up at the goal
+fn verify(stacks: &[Vec<Crate>], moves: &[Instruction], goal: &Goal, crane: Crane) -> bool {
+    let mut stacks = stacks.to_vec();
+    for move_ in moves {
+        let valid = (1..=stacks.len()).contains(&move_.from)
+            && (1..=stacks.len()).contains(&move_.to)
+            && move_.n <= stacks[move_.from - 1].len();
+        if !valid {
+            return false;
+        }
+        crane.apply(move_, &mut stacks);
+    }
+    goal.is_met(&stacks)
+}
+
+// The inverse of top_of_stack: find a short list of moves that takes the stacks to the goal.
+// Returns None if the goal can't be reached (or the planner can't find a way to reach it).
+fn plan(stacks: &[Vec<Crate>], goal: &Goal, crane: Crane) -> Option<Vec<Instruction>> {
+    let moves = match goal {
+        Goal::Arrangement(want) => {
+            let mut planner = Planner::new(stacks, crane);
+            planner.arrangement(want)?;
+            planner.moves
+        }
+        Goal::Tops(tops) => {
+            // The order matters since a crate we need can end up buried under one we already placed,
+            // try every order when there are few enough stacks and keep the shortest plan
+            let todo: Vec<usize> = (0..tops.len()).filter(|&i| tops[i].is_some()).collect();
+            let orders: Vec<Vec<usize>> = if todo.len() <= 6 {
+                todo.iter().copied().permutations(todo.len()).collect()
+            } else {
+                vec![todo.clone(), todo.iter().rev().copied().collect()]
+            };
+            // Fixing one stack at a time can paint itself into a corner, so also plan for
+            // a full arrangement with the right tops, which always works with three or more stacks
+            let fallback = arrangement_for(stacks, tops).and_then(|want| {
+                let mut planner = Planner::new(stacks, crane);
+                planner.arrangement(&want).map(|_| planner.moves)
+            });
+            orders
+                .iter()
+                .filter_map(|order| {
+                    let mut planner = Planner::new(stacks, crane);
+                    planner.tops(tops, order).map(|_| planner.moves)
+                })
+                .chain(fallback)
+                .min_by_key(|moves| moves.len())?
+        }
+    };
+    verify(stacks, &moves, goal, crane).then_some(moves)
+}
+
+fn parse_stacks(input: &str) -> Vec<Vec<Crate>> {
     // We need to take an input like:
     //     [d]
     // [a] [b] [c]
@@ -44,26 +323,43 @@ fn parse_stacks(input: &str) -> Vec<Vec<char>> {
     // 1 => [a]
     // 2 => [b, d]
     // 3 => [c]
-    let num_stacks = input.split_whitespace().last().unwrap().parse().unwrap();
-    let mut stacks = vec![Vec::new(); num_stacks];
-    for line in input.lines().rev() {
-        for (idx, mut chunk) in line.chars().chunks(4).into_iter().enumerate() {
-            let second = chunk.nth(1).unwrap();
-            if second.is_alphabetic() {
-                stacks[idx].push(second);
-            }
+    // Columns aren't always 4 wide (labels like 10 or crates like [AB] push them apart),
+    // so each crate goes to the stack whose label in the footer is closest to it
+    let mut lines = input.lines().rev().skip_while(|line| line.trim().is_empty());
+    let footer = lines.next().unwrap();
+    let labels: Vec<f64> = Regex::new(r"\S+").unwrap()
+        .find_iter(footer)
+        .map(|m| column(footer, m.start(), m.end()))
+        .collect();
+    let crate_re = Regex::new(r"\[([^\]]+)\]").unwrap();
+    let mut stacks = vec![Vec::new(); labels.len()];
+    for line in lines {
+        for m in crate_re.find_iter(line) {
+            let center = column(line, m.start(), m.end());
+            let idx = (0..labels.len())
+                .min_by(|&a, &b| (labels[a] - center).abs().total_cmp(&(labels[b] - center).abs()))
+                .unwrap();
+            stacks[idx].push(m.as_str()[1..m.as_str().len() - 1].to_string());
         }
     }
     stacks
 }
 
+// Center of a byte range of a line, counted in characters
+fn column(line: &str, start: usize, end: usize) -> f64 {
+    let from = line[..start].chars().count();
+    let to = from + line[start..end].chars().count();
+    (from + to) as f64 / 2.0
+}
+
 fn parse_moves(input: &str) -> Vec<Instruction> {
-    input.lines().map(Instruction::from).collect()
+    input.lines().filter(|line| !line.trim().is_empty()).map(Instruction::from).collect()
 }
 
-fn parse_input(input: &str) -> (Vec<Vec<char>>, Vec<Instruction>) {
-    // Picture and input are separated by a blank line
-    let mut parts = input.split("\n\n");
+fn parse_input(input: &str) -> (Vec<Vec<Crate>>, Vec<Instruction>) {
+    // Picture and input are separated by a blank line, which may have stray whitespace on it
+    let blank = Regex::new(r"\n[ \t\r]*\n").unwrap();
+    let mut parts = blank.splitn(input, 2);
     let stacks = parse_stacks(parts.next().unwrap());
     let moves = parse_moves(parts.next().unwrap());
     (stacks, moves)
@@ -75,7 +371,7 @@ fn top_of_stack(input: &str) -> String {
     for move_ in moves {
         move_.apply(&mut stacks);
     }
-    stacks.iter().map(|stack| stack.last().unwrap_or(&' ')).join("")
+    stacks.iter().map(|stack| stack.last().map_or(" ", String::as_str)).join("")
 }
 
 fn top_of_stack2(input: &str) -> String {
@@ -84,13 +380,33 @@ fn top_of_stack2(input: &str) -> String {
     for move_ in moves {
         move_.apply9001(&mut stacks);
     }
-    stacks.iter().map(|stack| stack.last().unwrap_or(&' ')).join("")
+    stacks.iter().map(|stack| stack.last().map_or(" ", String::as_str)).join("")
 }
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("{}", top_of_stack(&input));
-    println!("{}", top_of_stack2(&input));
+    println!("{}", top_of_stack(input));
+    println!("{}", top_of_stack2(input));
+
+    // day5 tops <tops, e.g. CMZ or AB,,C> [9001] or day5 stacks <drawing file> [9001]
+    // prints the moves that take the puzzle's starting stacks to the goal
+    let mut args = std::env::args().skip(1);
+    if let (Some(kind), Some(target)) = (args.next(), args.next()) {
+        let crane = match args.next().as_deref() {
+            Some("9001") => Crane::CrateMover9001,
+            _ => Crane::CrateMover9000,
+        };
+        let goal = match kind.as_str() {
+            "tops" => Goal::tops(&target),
+            "stacks" => Goal::Arrangement(parse_stacks(&std::fs::read_to_string(target).unwrap())),
+            _ => panic!("Unknown goal {}", kind),
+        };
+        let (stacks, _) = parse_input(input);
+        match plan(&stacks, &goal, crane) {
+            Some(moves) => moves.iter().for_each(|move_| println!("{}", move_)),
+            None => println!("No plan found"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -107,10 +423,15 @@ mod tests {
     move 2 from 2 to 1\n\
     move 1 from 1 to 2";
 
+    // One stack per string, one single character crate per char
+    fn crates(stacks: &[&str]) -> Vec<Vec<Crate>> {
+        stacks.iter().map(|stack| stack.chars().map(|c| c.to_string()).collect()).collect()
+    }
+
     #[test]
     fn test_parse() {
         let (stacks, moves) = parse_input(INPUT);
-        assert_eq!(stacks, vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]);
+        assert_eq!(stacks, crates(&["ZN", "MCD", "P"]));
         assert_eq!(moves, vec![
             Instruction { n: 1, from: 2, to: 1 },
             Instruction { n: 3, from: 1, to: 3 },
@@ -119,11 +440,40 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_parse_wide() {
+        // Labels past 9 and uneven trailing whitespace
+        let picture = "                                        [K]\n\
+        [A]                                 [J] [L]  \n\
+        [B] [C] [D] [E] [F] [G] [H] [I]     [M] [N]\n\
+         1   2   3   4   5   6   7   8   9  10  11 ";
+        let stacks = parse_stacks(picture);
+        assert_eq!(stacks, crates(&["BA", "C", "D", "E", "F", "G", "H", "I", "", "MJ", "NLK"]));
+
+        // Multi character crates with labels centered under them
+        let picture = "       [XY]\n\
+        [AB]   [CD]   [EFG]\n \
+          1      2      3   ";
+        let stacks = parse_stacks(picture);
+        assert_eq!(stacks, vec![
+            vec!["AB".to_string()],
+            vec!["CD".to_string(), "XY".to_string()],
+            vec!["EFG".to_string()],
+        ]);
+    }
+
+    #[test]
+    fn test_parse_blank_separator() {
+        let input = INPUT.replace("\n\n", "\n   \n");
+        assert_eq!(parse_input(&input), parse_input(INPUT));
+        assert_eq!(top_of_stack(&input), "CMZ");
+    }
+
     #[test]
     fn test_apply() {
-        let mut stacks = vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']];
+        let mut stacks = crates(&["ZN", "MCD", "P"]);
         Instruction { n: 1, from: 2, to: 1 }.apply(&mut stacks);
-        assert_eq!(stacks, vec![vec!['Z', 'N', 'D'], vec!['M', 'C'], vec!['P']]);
+        assert_eq!(stacks, crates(&["ZND", "MC", "P"]));
     }
 
     #[test]
@@ -136,4 +486,74 @@ mod tests {
         assert_eq!(top_of_stack2(INPUT), "MCD");
     }
 
+    #[test]
+    fn test_display() {
+        let move_ = Instruction { n: 3, from: 1, to: 3 };
+        assert_eq!(move_.to_string(), "move 3 from 1 to 3");
+        assert_eq!(Instruction::from(move_.to_string().as_str()), move_);
+    }
+
+    #[test]
+    fn test_plan_arrangement() {
+        let (stacks, moves) = parse_input(INPUT);
+        for crane in [Crane::CrateMover9000, Crane::CrateMover9001] {
+            let mut want = stacks.clone();
+            for move_ in &moves {
+                crane.apply(move_, &mut want);
+            }
+            let goal = Goal::Arrangement(want);
+            let plan = plan(&stacks, &goal, crane).unwrap();
+            assert!(verify(&stacks, &plan, &goal, crane));
+        }
+    }
+
+    #[test]
+    fn test_plan_reorder() {
+        let stacks = crates(&["ABC", "", ""]);
+        let goal = Goal::Arrangement(crates(&["", "CBA", ""]));
+        // The 9000 flips the whole stack in one go
+        assert_eq!(plan(&stacks, &goal, Crane::CrateMover9000).unwrap(), vec![
+            Instruction { n: 3, from: 1, to: 2 },
+        ]);
+        let plan = plan(&stacks, &goal, Crane::CrateMover9001).unwrap();
+        assert!(verify(&stacks, &plan, &goal, Crane::CrateMover9001));
+    }
+
+    #[test]
+    fn test_plan_tops() {
+        let (stacks, _) = parse_input(INPUT);
+        for crane in [Crane::CrateMover9000, Crane::CrateMover9001] {
+            for tops in ["CMZ", "MCD", "Z  ", " PN", "DPM"] {
+                let goal = Goal::tops(tops);
+                let plan = plan(&stacks, &goal, crane).expect(tops);
+                assert!(verify(&stacks, &plan, &goal, crane), "{} {:?}", tops, crane);
+            }
+        }
+        // Already there
+        assert_eq!(plan(&stacks, &Goal::tops("NDP"), Crane::CrateMover9000), Some(vec![]));
+        // Digging Z out from under N takes a single 9000 move
+        assert_eq!(plan(&stacks, &Goal::tops(" Z "), Crane::CrateMover9000), Some(vec![
+            Instruction { n: 2, from: 1, to: 2 },
+        ]));
+        // Wide crates need the labels split up
+        assert_eq!(Goal::tops("C,M,Z"), Goal::tops("CMZ"));
+        assert_eq!(Goal::tops(",Z, "), Goal::tops(" Z "));
+        let stacks = vec![vec!["A".to_string()], vec!["CD".to_string(), "XY".to_string()], vec!["EFG".to_string()]];
+        let goal = Goal::tops("XY,,CD");
+        assert_eq!(goal, Goal::Tops(vec![Some("XY".to_string()), None, Some("CD".to_string())]));
+        let plan = plan(&stacks, &goal, Crane::CrateMover9000).unwrap();
+        assert!(verify(&stacks, &plan, &goal, Crane::CrateMover9000));
+    }
+
+    #[test]
+    fn test_plan_impossible() {
+        let (stacks, _) = parse_input(INPUT);
+        // There's only one Z
+        assert_eq!(plan(&stacks, &Goal::tops("ZZ "), Crane::CrateMover9000), None);
+        // Crates can't appear out of nowhere
+        let goal = Goal::Arrangement(crates(&["X", "", ""]));
+        assert_eq!(plan(&stacks, &goal, Crane::CrateMover9001), None);
+        // Wrong number of stacks
+        assert_eq!(plan(&stacks, &Goal::tops("NDPM"), Crane::CrateMover9001), None);
+    }
 }
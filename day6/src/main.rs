@@ -1,39 +1,229 @@
-use std::collections::HashSet;
+// Benchmarks need nightly, run them with `cargo +nightly bench -p day6 --features bench`
+#![cfg_attr(all(test, feature = "bench"), feature(test))]
+#[cfg(all(test, feature = "bench"))]
+extern crate test;
 
-const INPUT: &'static str = "srjsssgppnssqzszmzjmjdmmqwqcwqccslsjswjssgbsgsnggqtqnnjznndtndnhddfldfdsfswsjsjjptjpttlwlccpnpgngcncnscncsnnwrwmwggsgdgssvbsstzsstqqrjqqlsqlsqscschhclhccznzcnnzzqppjbpjjqzzbwbqqzdqdjjqtjtbtgtqgtgdgwwsjjbwjjzssrsvsttgqtgglgplgpllcrcncssbrbgrbrllsqlsltthshrrnddwzdzdbbrppdvdqvvpvdvdsslbbmnbnjjrdrnntzzftzftttrsshpspvvrzzqzwzhzszbzjjwqwvqqglgflfwfrfprpddfvdffhzztwtppwwwztzsznzmnnbvbbtqqdhqdqdfffnjnmnlmljmlmnlnddzbbdgdqdnqqtjjtnthttvwwtrwwwgffqcfchfcfwcczhhgjhghpgpwgwffhghzghhtftntnnmlnmlmddqbbfwbfwbwvvfssrggptpltpllbcbzczzlccjgcgvcczjzvvvdtvdvqdqwdqdvdjvddjfdjjqmmfgfsftfzfwzwzfftmfmmqdqccjqcqwwflwlccpssvzzzbnbjnbjjgtgpttbwbrbwrwvrrwccgrgrvvpjvvznvvhddtdldpldppmlplltdllhqhpqhqqbpptgpgjgqjgjffvfdvdttsrsllhzlzrrdrnrjrddqbbjrrvrsstgsttjqjhjbhhnmndmmnrmnnrggmtgmmhshjjfqjjtqtstpstppggtzzrsrwwqffvzvzvqqggqsqtstszttpvtvvvddcncvncvchhnsnpsnnlmnnmqnqjjfwfccmwmbwbswbswwghhzrrptptbbjbtjjgdjgjrgrwgwlglblbnlnbbwnnpbnbmbtmtvvhjjlwlhhszslldwlwdllhjhghqghhqpplhhtjhhnhqhlhjhmmlhlmmrpptvvcczfcccwgcccpgpspdpccfhchffbjbzzwppfbbstbtltpllcssnnctnnqcqhhclhccvlvlffnjjwbwfbfttwvvdvnnnsrstrtmtfthtzzcvzvhzzpmpfpmmmwggdbdqdbbjnbbdqbbrnnnprrwbbcwbbpjjprjjzzvwwvrrthhqvhvnhhzmzszrszsjssclcjjbhjbhjbhjbjhhzbbzttnrnssrbrjbrjjmsmffdlflfzfbzzmtztdthdhldhlddnccgbbmtbbsbzsbzzcsszpzfffprfpfzfrzzhvhffsmmqtjwgjbzhnmrslrmgfjpqcllcgsjdhrshqtlgmqqtfmswfzwtnrswtzdjzclcfmltqgdhcsgvzrdltgfbtqclpppvbqnbqmlhmdbsjbwbdllzpnrwfhmnlgvdwsdjsznnhqzhwntjvcpzdrfwmwwdrttdvzspmbmqggmlmsvwgcjgpvcmplqwfjgpghnfpbctnfhcgngcbdmzhlpcnjpmczzsgfgrdftrzgvmmpdmgcrpcdrjsgczpfjnwpdjpntdngdjwctvcbsjmfwvtsrlhvpswppmfwrwzsbsgbjvljzqjjldmnqnmwsmqnmhmqhhttppbpqlvdcvdbhmbnjzztjrdjdzlvmbdrghtftwdpcwwblsjbgnzwtpztmtmnrpsvzfzncqmrvhcbqcvqvnlngdcllrqlbhjttnmjmhfhdzmjmplcfdqpmwblzsnmpczwcnggcwdvgnjcrrtmpwwqdqpvtbzpdbfnbfcfllntqjlslcsznjvzvsbntrtzwhcbtdmbmwttvhdvdtvrcmprcrrjlgsqddrsmwsrbtbpjrmlbrnsdrjfhjnqjtgjmhzjbjnprvmhtjcdbztwqmrlfflfmcslshtwmwhgvbdslgjhzjlglhllsdphlzngjfwfrlwpnqfhghnqhzhgrszbcwjvlrtmshntszsqplvfbccjwctgtfmqgqjdlgdbwhgvctqtcgfdwvqdwqzddmsbrpftzpqztgzbnplvhftmgpdthnrdhqbltbrmhpcqsfccmqzwmrbnbgbjspslwpjhdqspssqdtnssmjmvzwwfstgzzjrmfczdlznwqpdhbsjqddvffcgfhfdqdrlwcsgcsszdtpqbbsthpwbhdfzmgmcdggfcwcmzfjnfbzbccjhvwhbwfslnqnrwhgrwtlnmrmncnjtbjbdlmqsczppgbcmsdrwlrpjbgrmnhqqfhhsdhmdmpvvpjrnzsvzctmqhpzcvcjfgtlfvqvnvlnprmgrsvrrvtjfndqfsvqdsfbcwlbglmfhfhcfgqdfmclnzhdtppgzzsqgjqncqrbdhlhdjqwjpmbdnfmgdgwbwmnlngnmrhcgqwzvmbjzdvsspjwwdtpnvpftdlqlzfgtscfczsvbrtrqqpgqlvmrtddqplbzsswbgpdzpqfvqpqbndblghmdhmnctdnjbgglmrlvmrmsfgntfdwvqcvvlvbcnwrctvjqhmnsjqccwltbfqpqpmwsfvmnnfqmlmlcchqcdtbvqwcpptvfwrwtbdrlsgnwpmjgnwlprzqjlwqmtmjglbrzlgfbsghwqdmwhrcmfwdmzmflsbngtgndftdpzsqvgqdsfdhplmfcmwpbtvcdmpghmfwqjvhdhfpmbrqpvnbhlftgdtprlztrgnlcldfpjqjqdfrvqtcnzrtjcgzgsslzghlnfhwwjwzdsmpsczclrfmnqjvfmvsqpntsnnnlrfswqtrppzhqgjzlzvrrbhhfhchhvgztpgctcsgssvttszsrdwzwrbmwmspgqhmmfnzqqdbmnbltdmrsvqgddltwczbbjcmplncspgqgmzrndhttsrbvqbpbvhshfqrpqgmmdbhmmtccjcmntmpqhrvhnfnlqqbctsnfzjbphhqwmztgbhqqlbctbsfcszbggzrlcdhwddtjgtqhppzgjsqcddwjsngjrcdflmgwgfnzhjtcwgbqvpwmpgcpdwvqgswwfzcnjgmdpffmqczmsqgpthpmsjlwnrcbzrfshvwftzllwrmfccmlpjnmpjdfpcvjgjpznllmqjwpcflgqgdljtbbjvjlvhhmtvzfnjfnnwrvtlfdbhqphrjghtmlsrplqscsnvjvqdslsbsfzzrjfmchplzgjgdqvzhfphvsjfvnqlgmjfzhrdlmmvfntnzdvrnwqshsmtjnqmwzgpbbzszrsqcvlzjwnmgjhmfqrbvgmfqpswctmvpfcghvdqgstglmzvpfhzfzvhqqdmvrvrttlpwwhqgddzqlrvvdffqtznvlfgjhhmvbmtzjqnnhqzrtbzpqcwpngrdcndcgzwhzgtfwbmwbrpvvczczhwcqwsqzqbqvqftcswtcbzdbpccjhtwbpnwlwwwqwscptlwshrdbmmdcgrmpnnwgjwzszwwdwctfspbfqvdjqtrflshrqlbfgpnrmbszwjpcdzbggphgplcgvwljprzmtncsvfwqchttndhpnzmtdvtjqtcsddtqvcmztmjgwqvjhflrjjtnvfpjrnlvzvwvrpbhrzslmrqqzqhnzqnvtqppmncddphbwwsjczmphsrlltzndtqjgdlqgpnlfcvwntstmrgcrjzmmllpwldnwmwzpvzctmhszspcvtgnqthszzsmtdnzwtfddfctpjhscbqgwfpqmzpvqrzvtbrdjzrqprdgbpmzzfbgqcvcdtsfrffcpqwtvdwvtcqlcsdrzntgrhrspznndslmnvptlphpdqgbblfhmgbpmmfwqzlhvzshhpzgfjldqclngbcbrmmnqqvqmwdnjsglsggqfgjldqfbsqgtrwmpdffqlcwwlfhlpqfgwtssnjwzhgvtwqzmhmgwzwmcggmpmrzqrcsmflqsrbnzvdmjcdbnscstqrqhvddsbjpzwsvzswqhcqmgzlvfcnzjrrffzphmrvdbhqbrwpsfqvfqwhqhcgfvfsfttzcdsrjgjwcgvhllszmplmvgczqsbfldnbvrnqccbprjjdwhmqpdjjrnfdlhzdvlfmrldjlqclbjrrtjfsflphzdcdpfpr";
+use std::collections::VecDeque;
+use std::io::{self, Read};
 
-fn first_packet(input: &str) -> Option<usize> {
-    // Loop through a window of length 4
-    // Find the first window that contains all unique characters
-    // Return the total characters we had to process
-    for (i, window) in input.chars().collect::<Vec<char>>().windows(4).enumerate() {
-        if window.iter().collect::<HashSet<&char>>().len() == 4 {
-            return Some(i + 4);
+const INPUT: &str = "srjsssgppnssqzszmzjmjdmmqwqcwqccslsjswjssgbsgsnggqtqnnjznndtndnhddfldfdsfswsjsjjptjpttlwlccpnpgngcncnscncsnnwrwmwggsgdgssvbsstzsstqqrjqqlsqlsqscschhclhccznzcnnzzqppjbpjjqzzbwbqqzdqdjjqtjtbtgtqgtgdgwwsjjbwjjzssrsvsttgqtgglgplgpllcrcncssbrbgrbrllsqlsltthshrrnddwzdzdbbrppdvdqvvpvdvdsslbbmnbnjjrdrnntzzftzftttrsshpspvvrzzqzwzhzszbzjjwqwvqqglgflfwfrfprpddfvdffhzztwtppwwwztzsznzmnnbvbbtqqdhqdqdfffnjnmnlmljmlmnlnddzbbdgdqdnqqtjjtnthttvwwtrwwwgffqcfchfcfwcczhhgjhghpgpwgwffhghzghhtftntnnmlnmlmddqbbfwbfwbwvvfssrggptpltpllbcbzczzlccjgcgvcczjzvvvdtvdvqdqwdqdvdjvddjfdjjqmmfgfsftfzfwzwzfftmfmmqdqccjqcqwwflwlccpssvzzzbnbjnbjjgtgpttbwbrbwrwvrrwccgrgrvvpjvvznvvhddtdldpldppmlplltdllhqhpqhqqbpptgpgjgqjgjffvfdvdttsrsllhzlzrrdrnrjrddqbbjrrvrsstgsttjqjhjbhhnmndmmnrmnnrggmtgmmhshjjfqjjtqtstpstppggtzzrsrwwqffvzvzvqqggqsqtstszttpvtvvvddcncvncvchhnsnpsnnlmnnmqnqjjfwfccmwmbwbswbswwghhzrrptptbbjbtjjgdjgjrgrwgwlglblbnlnbbwnnpbnbmbtmtvvhjjlwlhhszslldwlwdllhjhghqghhqpplhhtjhhnhqhlhjhmmlhlmmrpptvvcczfcccwgcccpgpspdpccfhchffbjbzzwppfbbstbtltpllcssnnctnnqcqhhclhccvlvlffnjjwbwfbfttwvvdvnnnsrstrtmtfthtzzcvzvhzzpmpfpmmmwggdbdqdbbjnbbdqbbrnnnprrwbbcwbbpjjprjjzzvwwvrrthhqvhvnhhzmzszrszsjssclcjjbhjbhjbhjbjhhzbbzttnrnssrbrjbrjjmsmffdlflfzfbzzmtztdthdhldhlddnccgbbmtbbsbzsbzzcsszpzfffprfpfzfrzzhvhffsmmqtjwgjbzhnmrslrmgfjpqcllcgsjdhrshqtlgmqqtfmswfzwtnrswtzdjzclcfmltqgdhcsgvzrdltgfbtqclpppvbqnbqmlhmdbsjbwbdllzpnrwfhmnlgvdwsdjsznnhqzhwntjvcpzdrfwmwwdrttdvzspmbmqggmlmsvwgcjgpvcmplqwfjgpghnfpbctnfhcgngcbdmzhlpcnjpmczzsgfgrdftrzgvmmpdmgcrpcdrjsgczpfjnwpdjpntdngdjwctvcbsjmfwvtsrlhvpswppmfwrwzsbsgbjvljzqjjldmnqnmwsmqnmhmqhhttppbpqlvdcvdbhmbnjzztjrdjdzlvmbdrghtftwdpcwwblsjbgnzwtpztmtmnrpsvzfzncqmrvhcbqcvqvnlngdcllrqlbhjttnmjmhfhdzmjmplcfdqpmwblzsnmpczwcnggcwdvgnjcrrtmpwwqdqpvtbzpdbfnbfcfllntqjlslcsznjvzvsbntrtzwhcbtdmbmwttvhdvdtvrcmprcrrjlgsqddrsmwsrbtbpjrmlbrnsdrjfhjnqjtgjmhzjbjnprvmhtjcdbztwqmrlfflfmcslshtwmwhgvbdslgjhzjlglhllsdphlzngjfwfrlwpnqfhghnqhzhgrszbcwjvlrtmshntszsqplvfbccjwctgtfmqgqjdlgdbwhgvctqtcgfdwvqdwqzddmsbrpftzpqztgzbnplvhftmgpdthnrdhqbltbrmhpcqsfccmqzwmrbnbgbjspslwpjhdqspssqdtnssmjmvzwwfstgzzjrmfczdlznwqpdhbsjqddvffcgfhfdqdrlwcsgcsszdtpqbbsthpwbhdfzmgmcdggfcwcmzfjnfbzbccjhvwhbwfslnqnrwhgrwtlnmrmncnjtbjbdlmqsczppgbcmsdrwlrpjbgrmnhqqfhhsdhmdmpvvpjrnzsvzctmqhpzcvcjfgtlfvqvnvlnprmgrsvrrvtjfndqfsvqdsfbcwlbglmfhfhcfgqdfmclnzhdtppgzzsqgjqncqrbdhlhdjqwjpmbdnfmgdgwbwmnlngnmrhcgqwzvmbjzdvsspjwwdtpnvpftdlqlzfgtscfczsvbrtrqqpgqlvmrtddqplbzsswbgpdzpqfvqpqbndblghmdhmnctdnjbgglmrlvmrmsfgntfdwvqcvvlvbcnwrctvjqhmnsjqccwltbfqpqpmwsfvmnnfqmlmlcchqcdtbvqwcpptvfwrwtbdrlsgnwpmjgnwlprzqjlwqmtmjglbrzlgfbsghwqdmwhrcmfwdmzmflsbngtgndftdpzsqvgqdsfdhplmfcmwpbtvcdmpghmfwqjvhdhfpmbrqpvnbhlftgdtprlztrgnlcldfpjqjqdfrvqtcnzrtjcgzgsslzghlnfhwwjwzdsmpsczclrfmnqjvfmvsqpntsnnnlrfswqtrppzhqgjzlzvrrbhhfhchhvgztpgctcsgssvttszsrdwzwrbmwmspgqhmmfnzqqdbmnbltdmrsvqgddltwczbbjcmplncspgqgmzrndhttsrbvqbpbvhshfqrpqgmmdbhmmtccjcmntmpqhrvhnfnlqqbctsnfzjbphhqwmztgbhqqlbctbsfcszbggzrlcdhwddtjgtqhppzgjsqcddwjsngjrcdflmgwgfnzhjtcwgbqvpwmpgcpdwvqgswwfzcnjgmdpffmqczmsqgpthpmsjlwnrcbzrfshvwftzllwrmfccmlpjnmpjdfpcvjgjpznllmqjwpcflgqgdljtbbjvjlvhhmtvzfnjfnnwrvtlfdbhqphrjghtmlsrplqscsnvjvqdslsbsfzzrjfmchplzgjgdqvzhfphvsjfvnqlgmjfzhrdlmmvfntnzdvrnwqshsmtjnqmwzgpbbzszrsqcvlzjwnmgjhmfqrbvgmfqpswctmvpfcghvdqgstglmzvpfhzfzvhqqdmvrvrttlpwwhqgddzqlrvvdffqtznvlfgjhhmvbmtzjqnnhqzrtbzpqcwpngrdcndcgzwhzgtfwbmwbrpvvczczhwcqwsqzqbqvqftcswtcbzdbpccjhtwbpnwlwwwqwscptlwshrdbmmdcgrmpnnwgjwzszwwdwctfspbfqvdjqtrflshrqlbfgpnrmbszwjpcdzbggphgplcgvwljprzmtncsvfwqchttndhpnzmtdvtjqtcsddtqvcmztmjgwqvjhflrjjtnvfpjrnlvzvwvrpbhrzslmrqqzqhnzqnvtqppmncddphbwwsjczmphsrlltzndtqjgdlqgpnlfcvwntstmrgcrjzmmllpwldnwmwzpvzctmhszspcvtgnqthszzsmtdnzwtfddfctpjhscbqgwfpqmzpvqrzvtbrdjzrqprdgbpmzzfbgqcvcdtsfrffcpqwtvdwvtcqlcsdrzntgrhrspznndslmnvptlphpdqgbblfhmgbpmmfwqzlhvzshhpzgfjldqclngbcbrmmnqqvqmwdnjsglsggqfgjldqfbsqgtrwmpdffqlcwwlfhlpqfgwtssnjwzhgvtwqzmhmgwzwmcggmpmrzqrcsmflqsrbnzvdmjcdbnscstqrqhvddsbjpzwsvzswqhcqmgzlvfcnzjrrffzphmrvdbhqbrwpsfqvfqwhqhcgfvfsfttzcdsrjgjwcgvhllszmplmvgczqsbfldnbvrnqccbprjjdwhmqpdjjrnfdlhzdvlfmrldjlqclbjrrtjfsflphzdcdpfpr";
+
+// Rolling window over the last `size` bytes.
+// Keeps a count of every byte in the window and how many bytes appear more than once,
+// so each step only has to look at the byte coming in and the one falling out
+struct Detector {
+    size: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    repeated: usize,
+}
+
+impl Detector {
+    fn new(size: usize) -> Self {
+        assert!(size > 0, "marker size must be at least 1");
+        Self { size, window: VecDeque::with_capacity(size), counts: [0; 256], repeated: 0 }
+    }
+
+    // True when this byte completes a window of distinct bytes,
+    // the window then starts over so markers never overlap
+    fn push(&mut self, b: u8) -> bool {
+        if self.window.len() == self.size {
+            let out = self.window.pop_front().unwrap() as usize;
+            self.counts[out] -= 1;
+            if self.counts[out] == 1 {
+                self.repeated -= 1;
+            }
         }
+        self.window.push_back(b);
+        self.counts[b as usize] += 1;
+        if self.counts[b as usize] == 2 {
+            self.repeated += 1;
+        }
+        if self.window.len() == self.size && self.repeated == 0 {
+            self.reset();
+            return true;
+        }
+        false
+    }
+
+    fn reset(&mut self) {
+        for b in self.window.drain(..) {
+            self.counts[b as usize] = 0;
+        }
+        self.repeated = 0;
     }
-    None
 }
 
-fn message_marker(input: &str) -> Option<usize> {
-    // Loop through a window of length 14
-    // Find the first window that contains all unique characters
-    // Return the total characters we had to process
-    for (i, window) in input.chars().collect::<Vec<char>>().windows(14).enumerate() {
-        if window.iter().collect::<HashSet<&char>>().len() == 14 {
-            return Some(i + 14);
+// Number of bytes processed by the end of the first window of `size` distinct bytes
+fn marker(input: &str, size: usize) -> Option<usize> {
+    if size == 0 {
+        return Some(0);
+    }
+    let mut detector = Detector::new(size);
+    input.bytes().position(|b| detector.push(b)).map(|i| i + 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Packet,
+    Message,
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    // A marker of the given kind ends after this many bytes of the stream
+    Marker(Kind, usize),
+    // Everything after a marker up to the next marker of the same kind (or the end of the stream)
+    Payload(Kind, Vec<u8>),
+}
+
+// Splits the stream for one kind of marker
+struct Splitter {
+    kind: Kind,
+    detector: Detector,
+    // None until the first marker, anything before it is noise
+    payload: Option<Vec<u8>>,
+}
+
+impl Splitter {
+    fn new(kind: Kind, size: usize) -> Self {
+        Self { kind, detector: Detector::new(size), payload: None }
+    }
+
+    fn push(&mut self, b: u8, pos: usize, events: &mut Vec<Event>) {
+        if let Some(payload) = self.payload.as_mut() {
+            payload.push(b);
+        }
+        if self.detector.push(b) {
+            if let Some(mut payload) = self.payload.take() {
+                // The marker itself isn't part of the payload before it
+                payload.truncate(payload.len() - self.detector.size);
+                events.push(Event::Payload(self.kind, payload));
+            }
+            events.push(Event::Marker(self.kind, pos));
+            self.payload = Some(Vec::new());
+        }
+    }
+
+    fn finish(&mut self, events: &mut Vec<Event>) {
+        if let Some(payload) = self.payload.take() {
+            events.push(Event::Payload(self.kind, payload));
         }
     }
-    None
+}
+
+// Finds every start-of-packet and start-of-message marker as the bytes come in.
+// Chunks can be split anywhere, all the state carries over to the next feed
+struct Decoder {
+    packets: Splitter,
+    messages: Splitter,
+    pos: usize,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Self::with_sizes(4, 14)
+    }
+
+    fn with_sizes(packet: usize, message: usize) -> Self {
+        Self {
+            packets: Splitter::new(Kind::Packet, packet),
+            messages: Splitter::new(Kind::Message, message),
+            pos: 0,
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        let mut events = Vec::new();
+        for &b in chunk {
+            self.pos += 1;
+            self.packets.push(b, self.pos, &mut events);
+            self.messages.push(b, self.pos, &mut events);
+        }
+        events
+    }
+
+    // End of the stream, hands out whatever came after the last markers
+    fn finish(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        self.packets.finish(&mut events);
+        self.messages.finish(&mut events);
+        events
+    }
+
+    fn events<R: Read>(self, reader: R) -> Events<R> {
+        Events { reader, decoder: self, pending: VecDeque::new(), done: false }
+    }
+}
+
+// Events from a reader, read a chunk at a time as they're needed
+struct Events<R: Read> {
+    reader: R,
+    decoder: Decoder,
+    pending: VecDeque<Event>,
+    done: bool,
+}
+
+impl<R: Read> Iterator for Events<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = [0; 4096];
+        while self.pending.is_empty() && !self.done {
+            match self.reader.read(&mut buf) {
+                Ok(0) => {
+                    self.done = true;
+                    self.pending.extend(self.decoder.finish());
+                }
+                Ok(n) => self.pending.extend(self.decoder.feed(&buf[..n])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+fn first_packet(input: &str) -> Option<usize> {
+    marker(input, 4)
+}
+
+fn message_marker(input: &str) -> Option<usize> {
+    marker(input, 14)
 }
 
 fn main() {
     println!("{}", first_packet(INPUT).unwrap());
     println!("{}", message_marker(INPUT).unwrap());
+
+    // day6 stream decodes stdin as it arrives and prints every marker
+    if std::env::args().nth(1).as_deref() == Some("stream") {
+        for event in Decoder::new().events(io::stdin().lock()) {
+            match event.unwrap() {
+                Event::Marker(kind, pos) => println!("{:?} marker at {}", kind, pos),
+                Event::Payload(kind, data) => println!("{:?}: {}", kind, String::from_utf8_lossy(&data)),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
+
+    // The original HashSet per window version, kept to check against and benchmark
+    fn naive_marker(input: &str, size: usize) -> Option<usize> {
+        for (i, window) in input.chars().collect::<Vec<char>>().windows(size).enumerate() {
+            if window.iter().collect::<HashSet<&char>>().len() == size {
+                return Some(i + size);
+            }
+        }
+        None
+    }
 
     #[test]
     fn test_first_packet() {
@@ -48,4 +238,132 @@ mod tests {
         assert_eq!(message_marker("bvwbjplbgvbhsrlpgdmjqwftvncz"), Some(23));
         assert_eq!(message_marker("nppdvjthqldpwncqszvftbrmjlhg"), Some(23));
     }
+
+    #[test]
+    fn test_marker() {
+        let samples = [
+            "mjqjpqmgbljsphdztnvjfqwrcgsmlb",
+            "bvwbjplbgvbhsrlpgdmjqwftvncz",
+            "nppdvjthqldpwncqszvftbrmjlhg",
+            "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg",
+            "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw",
+            "aaaaaaaa",
+            "",
+        ];
+        for input in samples {
+            for size in 1..=16 {
+                assert_eq!(marker(input, size), naive_marker(input, size), "{} {}", input, size);
+            }
+        }
+        assert_eq!(marker("abc", 0), Some(0));
+        assert_eq!(marker("abcd", 5), None);
+    }
+
+    #[test]
+    fn test_input() {
+        assert_eq!(first_packet(INPUT), naive_marker(INPUT, 4));
+        assert_eq!(message_marker(INPUT), naive_marker(INPUT, 14));
+    }
+
+    #[cfg(feature = "bench")]
+    mod bench {
+        use super::*;
+        use test::{black_box, Bencher};
+
+        #[bench]
+        fn bench_marker(b: &mut Bencher) {
+            b.iter(|| (marker(black_box(INPUT), 4), marker(black_box(INPUT), 14)));
+        }
+
+        #[bench]
+        fn bench_naive_marker(b: &mut Bencher) {
+            b.iter(|| (naive_marker(black_box(INPUT), 4), naive_marker(black_box(INPUT), 14)));
+        }
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut decoder = Decoder::new();
+        let events = decoder.feed(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb");
+        assert_eq!(events, vec![
+            Event::Marker(Kind::Packet, 7),
+            Event::Payload(Kind::Packet, vec![]),
+            Event::Marker(Kind::Packet, 11),
+            Event::Payload(Kind::Packet, vec![]),
+            Event::Marker(Kind::Packet, 15),
+            Event::Payload(Kind::Packet, vec![]),
+            Event::Marker(Kind::Packet, 19),
+            Event::Marker(Kind::Message, 19),
+            Event::Payload(Kind::Packet, vec![]),
+            Event::Marker(Kind::Packet, 23),
+            Event::Payload(Kind::Packet, vec![]),
+            Event::Marker(Kind::Packet, 27),
+        ]);
+        assert_eq!(decoder.finish(), vec![
+            Event::Payload(Kind::Packet, b"mlb".to_vec()),
+            Event::Payload(Kind::Message, b"jfqwrcgsmlb".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn test_decoder_payloads() {
+        let mut decoder = Decoder::with_sizes(2, 100);
+        let mut events = decoder.feed(b"aababbbaxxyyy");
+        events.extend(decoder.finish());
+        // a [ab] [ab] b [ba] x [xy] yy
+        assert_eq!(events, vec![
+            Event::Marker(Kind::Packet, 3),
+            Event::Payload(Kind::Packet, vec![]),
+            Event::Marker(Kind::Packet, 5),
+            Event::Payload(Kind::Packet, b"b".to_vec()),
+            Event::Marker(Kind::Packet, 8),
+            Event::Payload(Kind::Packet, b"x".to_vec()),
+            Event::Marker(Kind::Packet, 11),
+            Event::Payload(Kind::Packet, b"yy".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn test_decoder_chunks() {
+        let whole = {
+            let mut decoder = Decoder::new();
+            let mut events = decoder.feed(INPUT.as_bytes());
+            events.extend(decoder.finish());
+            events
+        };
+        for size in [1, 2, 3, 13, 14, 15, 1000] {
+            let mut decoder = Decoder::new();
+            let mut events: Vec<Event> = INPUT.as_bytes().chunks(size).flat_map(|chunk| decoder.feed(chunk)).collect();
+            events.extend(decoder.finish());
+            assert_eq!(events, whole, "chunks of {}", size);
+        }
+        assert_eq!(whole.first(), Some(&Event::Marker(Kind::Packet, first_packet(INPUT).unwrap())));
+        let message = whole.iter().find(|e| matches!(e, Event::Marker(Kind::Message, _)));
+        assert_eq!(message, Some(&Event::Marker(Kind::Message, message_marker(INPUT).unwrap())));
+    }
+
+    // Hands out at most 3 bytes per read like a slow device
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_decoder_reader() {
+        let events: Vec<Event> = Decoder::new().events(INPUT.as_bytes()).map(Result::unwrap).collect();
+        let trickled: Vec<Event> = Decoder::new().events(Trickle(INPUT.as_bytes())).map(Result::unwrap).collect();
+        assert_eq!(events, trickled);
+        // Payloads and markers add back up to the stream after the first marker
+        let packets: usize = events.iter().map(|e| match e {
+            Event::Payload(Kind::Packet, data) => data.len() + 4,
+            _ => 0,
+        }).sum();
+        assert_eq!(packets, INPUT.len() - first_packet(INPUT).unwrap() + 4);
+    }
 }
@@ -0,0 +1,233 @@
+use crate::filesystem::{FileSystem, NodeId, ROOT};
+
+pub const CAPACITY: usize = 70_000_000;
+pub const REQUIRED: usize = 30_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    // The puzzle's answer, the smallest single directory that frees enough
+    Single,
+    // Any number of directories, as few bytes deleted as possible
+    FewestBytes,
+    // As few directories as possible, then as few bytes as possible
+    FewestDeletions,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Plan {
+    pub dirs: Vec<NodeId>,
+    pub freed: usize,
+}
+
+impl Plan {
+    fn new(dirs: Vec<NodeId>, sizes: &[usize]) -> Self {
+        let freed = dirs.iter().map(|&dir| sizes[dir]).sum();
+        Self { dirs, freed }
+    }
+}
+
+// Picks directories to delete so that `required` bytes are free on a disk of `capacity`.
+// None of them are inside another and the root is never one of them.
+// None if even deleting every directory under the root isn't enough
+pub fn plan(fs: &FileSystem, capacity: usize, required: usize, strategy: Strategy) -> Option<Plan> {
+    let sizes = fs.sizes();
+    let need = (sizes[ROOT] + required).saturating_sub(capacity);
+    if need == 0 {
+        return Some(Plan { dirs: vec![], freed: 0 });
+    }
+    // Everything directly under the root is the most any set of them can free
+    let most: usize = subdirs(fs, &sizes, ROOT).iter().map(|&dir| sizes[dir]).sum();
+    if most < need {
+        return None;
+    }
+    let mut dirs: Vec<NodeId> = fs.dirs().filter(|&dir| dir != ROOT).collect();
+    match strategy {
+        Strategy::Single => dirs
+            .into_iter()
+            .filter(|&dir| sizes[dir] >= need)
+            .min_by_key(|&dir| sizes[dir])
+            .map(|dir| Plan::new(vec![dir], &sizes)),
+        Strategy::FewestBytes => {
+            let mut frontier = subdirs(fs, &sizes, ROOT);
+            let mut prune = Prune { fs, sizes: &sizes, need, chosen: vec![], best: None };
+            prune.run(&mut frontier, 0, most);
+            prune.best.map(|(_, dirs)| Plan::new(dirs, &sizes))
+        }
+        Strategy::FewestDeletions => {
+            // Biggest first so the search can stop as soon as the rest can't add up
+            dirs.sort_by_key(|&dir| std::cmp::Reverse(sizes[dir]));
+            (1..=dirs.len()).find_map(|count| {
+                let mut search = Search { fs, sizes: &sizes, dirs: &dirs, need, chosen: vec![], best: None };
+                search.run(0, count, 0);
+                search.best.map(|dirs| Plan::new(dirs, &sizes))
+            })
+        }
+    }
+}
+
+// Directories directly inside dir, smallest first
+fn subdirs(fs: &FileSystem, sizes: &[usize], dir: NodeId) -> Vec<NodeId> {
+    let mut dirs: Vec<_> = fs.children(dir).filter(|&child| fs.is_dir(child)).collect();
+    dirs.sort_by_key(|&dir| sizes[dir]);
+    dirs
+}
+
+// Branch and bound over the tree. The frontier holds the directories still to decide on,
+// each of them is either deleted whole or opened up so its subdirectories join the frontier.
+// `rest` is everything the frontier could still free, so a branch that can't reach `need`
+// even with all of it is dropped, as is one already freeing more than the best so far
+struct Prune<'a> {
+    fs: &'a FileSystem,
+    sizes: &'a [usize],
+    need: usize,
+    chosen: Vec<NodeId>,
+    best: Option<(usize, Vec<NodeId>)>,
+}
+
+impl Prune<'_> {
+    fn run(&mut self, frontier: &mut Vec<NodeId>, sum: usize, rest: usize) {
+        if sum >= self.need {
+            if self.best.as_ref().is_none_or(|&(best, _)| sum < best) {
+                self.best = Some((sum, self.chosen.clone()));
+            }
+            return;
+        }
+        // Can't get there, or nothing can beat freeing exactly what's needed
+        if sum + rest < self.need || self.best.as_ref().is_some_and(|&(best, _)| best == self.need) {
+            return;
+        }
+        // Biggest on top so the first plans found are close to need and the rest prune well
+        let Some(dir) = frontier.pop() else { return };
+        let size = self.sizes[dir];
+        if self.best.as_ref().is_none_or(|&(best, _)| sum + size < best) {
+            self.chosen.push(dir);
+            self.run(frontier, sum + size, rest - size);
+            self.chosen.pop();
+        }
+        let len = frontier.len();
+        let inside = subdirs(self.fs, self.sizes, dir);
+        let opened: usize = inside.iter().map(|&dir| self.sizes[dir]).sum();
+        frontier.extend(inside);
+        self.run(frontier, sum, rest - size + opened);
+        frontier.truncate(len);
+        frontier.push(dir);
+    }
+}
+
+// Depth first search for the smallest total using at most `count` directories
+struct Search<'a> {
+    fs: &'a FileSystem,
+    sizes: &'a [usize],
+    dirs: &'a [NodeId],
+    need: usize,
+    chosen: Vec<NodeId>,
+    best: Option<Vec<NodeId>>,
+}
+
+impl Search<'_> {
+    fn total(&self, dirs: &[NodeId]) -> usize {
+        dirs.iter().map(|&dir| self.sizes[dir]).sum()
+    }
+
+    fn run(&mut self, start: usize, left: usize, sum: usize) {
+        if sum >= self.need {
+            if self.best.as_ref().is_none_or(|best| sum < self.total(best)) {
+                self.best = Some(self.chosen.clone());
+            }
+            return;
+        }
+        for idx in start..self.dirs.len() {
+            let dir = self.dirs[idx];
+            let size = self.sizes[dir];
+            // Sorted biggest first, nothing further on can make up the difference
+            if sum + left * size < self.need {
+                break;
+            }
+            // Anything with this one in it is already worse
+            if self.best.as_ref().is_some_and(|best| sum + size >= self.total(best)) {
+                continue;
+            }
+            if self.chosen.iter().any(|&other| self.fs.contains(other, dir) || self.fs.contains(dir, other)) {
+                continue;
+            }
+            self.chosen.push(dir);
+            self.run(idx + 1, left - 1, sum + size);
+            self.chosen.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = include_str!("test.txt");
+
+    fn paths(fs: &FileSystem, plan: &Plan) -> Vec<String> {
+        let mut paths: Vec<_> = plan.dirs.iter().map(|&dir| fs.path(dir)).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_example() {
+        let fs = FileSystem::from(INPUT);
+        for strategy in [Strategy::Single, Strategy::FewestBytes, Strategy::FewestDeletions] {
+            let plan = plan(&fs, CAPACITY, REQUIRED, strategy).unwrap();
+            assert_eq!(plan.freed, 24933642);
+            assert_eq!(paths(&fs, &plan), vec!["/d"]);
+        }
+        // Already enough room
+        assert_eq!(plan(&fs, 100_000_000, REQUIRED, Strategy::Single), Some(Plan { dirs: vec![], freed: 0 }));
+        // Deleting the most we can, /a and /d, still isn't enough
+        assert_eq!(plan(&fs, 50_000_000, 28_000_000, Strategy::FewestBytes), None);
+        assert_eq!(plan(&fs, 50_000_000, 28_000_000, Strategy::FewestDeletions), None);
+    }
+
+    #[test]
+    fn test_strategies() {
+        // 50 + 20 + 20 + 20 on a disk that needs 60 freed
+        let fs = FileSystem::from("$ cd /\n$ ls\ndir w\ndir x\ndir y\ndir z\n$ cd w\n$ ls\n50 a\n$ cd /x\n$ ls\n20 b\n$ cd /y\n$ ls\n20 c\n$ cd /z\n$ ls\n20 d");
+        assert_eq!(plan(&fs, 110, 60, Strategy::Single), None);
+        let bytes = plan(&fs, 110, 60, Strategy::FewestBytes).unwrap();
+        assert_eq!((bytes.freed, paths(&fs, &bytes)), (60, vec!["/x".to_string(), "/y".to_string(), "/z".to_string()]));
+        let deletions = plan(&fs, 110, 60, Strategy::FewestDeletions).unwrap();
+        assert_eq!(deletions.freed, 70);
+        assert_eq!(deletions.dirs.len(), 2);
+    }
+
+    #[test]
+    fn test_nested() {
+        // /a is 50 with /a/b 45 inside, /c is 10, need 54
+        let fs = FileSystem::from("$ cd /\n$ ls\ndir a\ndir c\n$ cd a\n$ ls\n5 f\ndir b\n$ cd b\n$ ls\n45 g\n$ cd /c\n$ ls\n10 h");
+        let plan = plan(&fs, 60, 54, Strategy::FewestBytes).unwrap();
+        assert_eq!((plan.freed, paths(&fs, &plan)), (55, vec!["/a/b".to_string(), "/c".to_string()]));
+    }
+
+    #[test]
+    fn test_out_of_reach() {
+        // Six directories of six subdirectories each, gigabytes in every one, and a file in the root
+        // that can never be freed
+        let mut fs = FileSystem::new();
+        fs.add_file(ROOT, "pinned", 5_000_000_000);
+        for i in 0..6 {
+            let top = fs.add_dir(ROOT, &format!("d{}", i));
+            fs.add_file(top, "f", 1_000_000_007 * (i + 1));
+            for j in 0..6 {
+                let sub = fs.add_dir(top, &format!("s{}", j));
+                fs.add_file(sub, "f", 3_000_000_019 * (j + 1) + 7 * i);
+            }
+        }
+        let sizes = fs.sizes();
+        let most: usize = fs.children(ROOT).filter(|&id| fs.is_dir(id)).map(|dir| sizes[dir]).sum();
+        let capacity = sizes[ROOT];
+        for strategy in [Strategy::Single, Strategy::FewestBytes, Strategy::FewestDeletions] {
+            assert_eq!(plan(&fs, capacity, most + 1, strategy), None);
+        }
+        // Just within reach takes every one of them
+        for strategy in [Strategy::FewestBytes, Strategy::FewestDeletions] {
+            let plan = plan(&fs, capacity, most, strategy).unwrap();
+            assert_eq!((plan.freed, plan.dirs.len()), (most, 6));
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::filesystem::{FileSystem, Kind, ROOT};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+// Names that would turn into something else as a path or in a log
+fn check_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains(char::is_whitespace) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("can't use {:?} as a name", name)));
+    }
+    Ok(())
+}
+
+// Recreates the filesystem under target. Files are created with set_len so
+// they're sparse and don't take up their recorded size on any filesystem that supports it
+pub fn export(fs: &FileSystem, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    let mut stack = vec![(ROOT, target.to_path_buf())];
+    while let Some((dir, path)) = stack.pop() {
+        for child in fs.children(dir) {
+            let node = fs.node(child);
+            check_name(&node.name)?;
+            let path = path.join(&node.name);
+            match node.kind {
+                Kind::Dir(_) => {
+                    fs::create_dir_all(&path)?;
+                    stack.push((child, path));
+                }
+                Kind::File(size) => File::create(&path)?.set_len(size as u64)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+// The terminal session that would have shown us the directory: cd into every
+// directory, ls it and come back out, everything in name order. Symlinks are left out
+pub fn session(root: &Path) -> io::Result<String> {
+    let mut log = String::from("$ cd /\n");
+    walk(root, &mut log)?;
+    Ok(log)
+}
+
+fn walk(dir: &Path, log: &mut String) -> io::Result<()> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.path().symlink_metadata()?;
+        if meta.file_type().is_symlink() {
+            continue;
+        }
+        let name = entry.file_name().into_string().map_err(|name| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{:?} isn't valid UTF-8", name))
+        })?;
+        check_name(&name)?;
+        entries.push((name, meta));
+    }
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    log.push_str("$ ls\n");
+    for (name, meta) in &entries {
+        if meta.is_dir() {
+            log.push_str(&format!("dir {}\n", name));
+        } else {
+            log.push_str(&format!("{} {}\n", meta.len(), name));
+        }
+    }
+    for (name, meta) in &entries {
+        if meta.is_dir() {
+            log.push_str(&format!("$ cd {}\n", name));
+            walk(&dir.join(name), log)?;
+            log.push_str("$ cd ..\n");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query;
+
+    const INPUT: &str = include_str!("test.txt");
+
+    fn scratch(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("day7-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = scratch("round-trip");
+        let original = FileSystem::from(INPUT);
+        export(&original, &dir).unwrap();
+        assert_eq!(fs::metadata(dir.join("d/d.log")).unwrap().len(), 8033020);
+        assert!(dir.join("a/e").is_dir());
+
+        let log = session(&dir).unwrap();
+        let (copy, issues) = FileSystem::replay(&log);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(query::tree(&copy), query::tree(&original));
+    }
+
+    #[test]
+    fn test_session() {
+        let dir = scratch("session");
+        fs::create_dir_all(dir.join("b/c")).unwrap();
+        File::create(dir.join("a")).unwrap().set_len(10).unwrap();
+        File::create(dir.join("b/c/d")).unwrap().set_len(5).unwrap();
+        let log = session(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(log, "$ cd /\n$ ls\n10 a\ndir b\n$ cd b\n$ ls\ndir c\n$ cd c\n$ ls\n5 d\n$ cd ..\n$ cd ..\n");
+    }
+
+    #[test]
+    fn test_bad_names() {
+        let dir = scratch("bad-names");
+        let dots = FileSystem::from("$ cd /\n$ ls\n10 ..");
+        assert_eq!(export(&dots, &dir).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        File::create(dir.join("has space")).unwrap();
+        let err = session(&dir).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+pub type NodeId = usize;
+
+pub const ROOT: NodeId = 0;
+
+#[derive(Debug, PartialEq)]
+pub enum Kind {
+    // Children by name, kept sorted so listings come out the same every time
+    Dir(BTreeMap<String, NodeId>),
+    File(usize),
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub kind: Kind,
+}
+
+// Every node lives in one Vec and refers to the others by index.
+// A node is always added after its parent, so children have bigger ids than their parents
+#[derive(Debug)]
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node { name: String::new(), parent: None, kind: Kind::Dir(BTreeMap::new()) }],
+        }
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    // Sorted by name, nothing for a file
+    pub fn children(&self, dir: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let children = match &self.nodes[dir].kind {
+            Kind::Dir(children) => Some(children.values().copied()),
+            Kind::File(_) => None,
+        };
+        children.into_iter().flatten()
+    }
+
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[dir].kind {
+            Kind::Dir(children) => children.get(name).copied(),
+            Kind::File(_) => None,
+        }
+    }
+
+    fn add(&mut self, dir: NodeId, name: &str, kind: Kind) -> NodeId {
+        let id = self.nodes.len();
+        match &mut self.nodes[dir].kind {
+            Kind::Dir(children) => {
+                children.insert(name.to_string(), id);
+            }
+            Kind::File(_) => panic!("{} is not a directory", self.path(dir)),
+        }
+        self.nodes.push(Node { name: name.to_string(), parent: Some(dir), kind });
+        id
+    }
+
+    // Returns the existing directory if there already is one with that name
+    pub fn add_dir(&mut self, dir: NodeId, name: &str) -> NodeId {
+        match self.child(dir, name) {
+            Some(id) => id,
+            None => self.add(dir, name, Kind::Dir(BTreeMap::new())),
+        }
+    }
+
+    pub fn add_file(&mut self, dir: NodeId, name: &str, size: usize) -> NodeId {
+        match self.child(dir, name) {
+            Some(id) => id,
+            None => self.add(dir, name, Kind::File(size)),
+        }
+    }
+
+    // "/" for the root, "/a/e" further down
+    pub fn path(&self, id: NodeId) -> String {
+        let mut parts = Vec::new();
+        let mut id = Some(id);
+        while let Some(node) = id.map(|id| &self.nodes[id]) {
+            parts.push(node.name.as_str());
+            id = node.parent;
+        }
+        if parts.len() == 1 {
+            return "/".to_string();
+        }
+        parts.reverse();
+        parts.join("/")
+    }
+
+    // Whether id is somewhere under dir
+    pub fn contains(&self, dir: NodeId, id: NodeId) -> bool {
+        let mut parent = self.nodes[id].parent;
+        while let Some(p) = parent {
+            if p == dir {
+                return true;
+            }
+            parent = self.nodes[p].parent;
+        }
+        false
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, Kind::Dir(_))
+    }
+
+    pub fn dirs(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|&id| self.is_dir(id))
+    }
+
+    // Total size of every node, indexed by id.
+    // Walking backwards sees every child before its parent, so one pass is enough
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.nodes.len()];
+        for (id, node) in self.nodes.iter().enumerate().rev() {
+            if let Kind::File(size) = node.kind {
+                sizes[id] = size;
+            }
+            if let Some(parent) = node.parent {
+                sizes[parent] += sizes[id];
+            }
+        }
+        sizes
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Issue {
+    // ls ran again in a directory that was already listed, entries already seen are only counted once
+    RepeatedListing { line: usize, dir: String },
+    // The same name came up again as a different size, or as a file one time and a directory the next.
+    // The first one wins
+    Conflict { line: usize, path: String, was: String, now: String },
+    // cd into something the parent's listing never showed, it's added as an empty directory
+    UnknownDir { line: usize, path: String },
+    // cd into a file, the working directory stays where it was
+    NotADirectory { line: usize, path: String },
+    // A command other than cd <path> or ls, it's skipped
+    UnknownCommand { line: usize, command: String },
+    // A listing line that isn't "dir <name>" or "<size> <name>", it's skipped
+    BadEntry { line: usize, entry: String },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::RepeatedListing { line, dir } => write!(f, "line {}: {} was already listed", line, dir),
+            Issue::Conflict { line, path, was, now } => write!(f, "line {}: {} was {}, now {}", line, path, was, now),
+            Issue::UnknownDir { line, path } => write!(f, "line {}: cd into {} which was never listed", line, path),
+            Issue::NotADirectory { line, path } => write!(f, "line {}: cd into {} which is a file", line, path),
+            Issue::UnknownCommand { line, command } => write!(f, "line {}: unknown command {}", line, command),
+            Issue::BadEntry { line, entry } => write!(f, "line {}: can't read {} as a listing entry", line, entry),
+        }
+    }
+}
+
+impl FileSystem {
+    fn describe(&self, id: NodeId) -> String {
+        match self.nodes[id].kind {
+            Kind::Dir(_) => "a directory".to_string(),
+            Kind::File(size) => format!("a file of size {}", size),
+        }
+    }
+
+    // Replays a terminal session, building up everything cd and ls showed us
+    // and reporting anything in the log that doesn't add up
+    pub fn replay(log: &str) -> (FileSystem, Vec<Issue>) {
+        let mut fs = FileSystem::new();
+        let mut issues = Vec::new();
+        let mut listed = HashSet::new();
+        let mut cwd = ROOT;
+
+        for (idx, line) in log.lines().enumerate() {
+            let line_no = idx + 1;
+            let parts: Vec<_> = line.split_whitespace().collect();
+            match parts[..] {
+                ["$", "cd", path] => {
+                    // Absolute paths start over from the root and .. never goes above it
+                    let mut dir = if path.starts_with('/') { ROOT } else { cwd };
+                    for part in path.split('/') {
+                        dir = match part {
+                            "" | "." => dir,
+                            ".." => fs.nodes[dir].parent.unwrap_or(ROOT),
+                            name => match fs.child(dir, name) {
+                                Some(id) if fs.is_dir(id) => id,
+                                Some(id) => {
+                                    issues.push(Issue::NotADirectory { line: line_no, path: fs.path(id) });
+                                    dir = cwd;
+                                    break;
+                                }
+                                None => {
+                                    let id = fs.add_dir(dir, name);
+                                    issues.push(Issue::UnknownDir { line: line_no, path: fs.path(id) });
+                                    id
+                                }
+                            },
+                        };
+                    }
+                    cwd = dir;
+                }
+                ["$", "ls"] if !listed.insert(cwd) => {
+                    issues.push(Issue::RepeatedListing { line: line_no, dir: fs.path(cwd) });
+                }
+                ["$", "ls"] => {}
+                ["$", ..] => issues.push(Issue::UnknownCommand { line: line_no, command: line.to_string() }),
+                [kind, name] => {
+                    let is_dir = kind == "dir";
+                    let size = match kind.parse() {
+                        _ if is_dir => 0,
+                        Ok(size) => size,
+                        Err(_) => {
+                            issues.push(Issue::BadEntry { line: line_no, entry: line.to_string() });
+                            continue;
+                        }
+                    };
+                    match fs.child(cwd, name) {
+                        Some(id) => {
+                            let same = match fs.nodes[id].kind {
+                                Kind::Dir(_) => is_dir,
+                                Kind::File(was) => !is_dir && was == size,
+                            };
+                            if !same {
+                                let now = if is_dir { "a directory".to_string() } else { format!("a file of size {}", size) };
+                                issues.push(Issue::Conflict { line: line_no, path: fs.path(id), was: fs.describe(id), now });
+                            }
+                        }
+                        None if is_dir => {
+                            fs.add_dir(cwd, name);
+                        }
+                        None => {
+                            fs.add_file(cwd, name, size);
+                        }
+                    }
+                }
+                [] => {}
+                _ => issues.push(Issue::BadEntry { line: line_no, entry: line.to_string() }),
+            }
+        }
+        (fs, issues)
+    }
+}
+
+impl From<&str> for FileSystem {
+    fn from(log: &str) -> Self {
+        FileSystem::replay(log).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cd_paths() {
+        let (fs, issues) = FileSystem::replay("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\ndir e\n$ cd /a/e\n$ ls\n1 i\n$ cd ../../..\n$ ls\n$ cd a/./e/\n$ ls");
+        let a = fs.child(ROOT, "a").unwrap();
+        let e = fs.child(a, "e").unwrap();
+        assert_eq!(fs.path(e), "/a/e");
+        assert_eq!(fs.path(ROOT), "/");
+        // The last two listings are the root and e again
+        assert_eq!(issues, vec![
+            Issue::RepeatedListing { line: 11, dir: "/".to_string() },
+            Issue::RepeatedListing { line: 13, dir: "/a/e".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_repeated_listing() {
+        let once = FileSystem::from("$ cd /\n$ ls\n10 x\ndir a\n$ cd a\n$ ls\n5 y");
+        let (twice, issues) = FileSystem::replay("$ cd /\n$ ls\n10 x\ndir a\n$ ls\n10 x\ndir a\n$ cd a\n$ ls\n5 y\n$ ls\n5 y");
+        assert_eq!(twice.sizes(), once.sizes());
+        assert_eq!(twice.sizes()[ROOT], 15);
+        assert_eq!(issues, vec![
+            Issue::RepeatedListing { line: 5, dir: "/".to_string() },
+            Issue::RepeatedListing { line: 11, dir: "/a".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let (fs, issues) = FileSystem::replay("$ cd /\n$ ls\n10 x\ndir a\n$ ls\n20 x\n30 a");
+        assert_eq!(fs.sizes()[ROOT], 10);
+        assert_eq!(issues, vec![
+            Issue::RepeatedListing { line: 5, dir: "/".to_string() },
+            Issue::Conflict { line: 6, path: "/x".to_string(), was: "a file of size 10".to_string(), now: "a file of size 20".to_string() },
+            Issue::Conflict { line: 7, path: "/a".to_string(), was: "a directory".to_string(), now: "a file of size 30".to_string() },
+        ]);
+        assert_eq!(issues[1].to_string(), "line 6: /x was a file of size 10, now a file of size 20");
+    }
+
+    #[test]
+    fn test_malformed() {
+        let (fs, issues) = FileSystem::replay("$ cd /\n$ pwd\n$ cd\n$ ls\nbig x\n10 y\n1 2 3\n\n$ cd a b");
+        assert_eq!(fs.sizes()[ROOT], 10);
+        assert_eq!(issues, vec![
+            Issue::UnknownCommand { line: 2, command: "$ pwd".to_string() },
+            Issue::UnknownCommand { line: 3, command: "$ cd".to_string() },
+            Issue::BadEntry { line: 5, entry: "big x".to_string() },
+            Issue::BadEntry { line: 7, entry: "1 2 3".to_string() },
+            Issue::UnknownCommand { line: 9, command: "$ cd a b".to_string() },
+        ]);
+        assert_eq!(issues[2].to_string(), "line 5: can't read big x as a listing entry");
+    }
+
+    #[test]
+    fn test_bad_cd() {
+        let (fs, issues) = FileSystem::replay("$ cd /\n$ ls\n10 x\n$ cd x\n$ cd nowhere\n$ ls\n5 y");
+        let nowhere = fs.child(ROOT, "nowhere").unwrap();
+        assert_eq!(fs.sizes()[nowhere], 5);
+        assert_eq!(issues, vec![
+            Issue::NotADirectory { line: 4, path: "/x".to_string() },
+            Issue::UnknownDir { line: 5, path: "/nowhere".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_cd_root_mid_session() {
+        let fs = FileSystem::from("$ cd /\n$ cd a\n$ ls\n10 x\n$ cd /\n$ cd b\n$ ls\n20 y\n$ cd /a\n$ ls\n5 z");
+        let a = fs.child(ROOT, "a").unwrap();
+        let b = fs.child(ROOT, "b").unwrap();
+        let sizes = fs.sizes();
+        assert_eq!(sizes[ROOT], 35);
+        assert_eq!(sizes[a], 15);
+        assert_eq!(sizes[b], 20);
+        // No directory called "/" anywhere
+        assert_eq!(fs.dirs().count(), 3);
+    }
+
+    #[test]
+    fn test_files_keep_names() {
+        let fs = FileSystem::from("$ cd /\n$ ls\n14848514 b.txt\ndir d\n$ cd d\n$ ls\n8033020 d.log");
+        let d = fs.child(ROOT, "d").unwrap();
+        let log = fs.child(d, "d.log").unwrap();
+        assert_eq!(fs.nodes[log].kind, Kind::File(8033020));
+        assert_eq!(fs.path(log), "/d/d.log");
+        assert!(!fs.is_dir(log));
+    }
+}
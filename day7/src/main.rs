@@ -1,84 +1,99 @@
-use std::{collections::HashMap, hash::Hash};
+mod cleanup;
+mod disk;
+mod filesystem;
+mod query;
 
-fn sizes(input: &str) -> HashMap<String, usize> {
-    let mut sizes = HashMap::new();
-    let mut affected = Vec::new();
+use cleanup::{Strategy, CAPACITY, REQUIRED};
+use filesystem::FileSystem;
+use query::{Find, Type};
+use std::path::Path;
 
-    for line in input.lines() {
-        let parts: Vec<_> = line.split_whitespace().collect();
-        match parts[..] {
-            ["$", "cd", ".."] => {
-                affected.pop();
-            }
-            ["$", "cd", name] => {
-                affected.push(name);
-            }
-            ["$", "ls"] => {}
-            ["dir", _] => {}
-            [size, _name] => {
-                let size: usize = size.parse().unwrap();
-                for idx in 0..affected.len() {
-                    let name = affected[..=idx].join("/");
-                    *sizes.entry(name).or_insert(0) += size;
-                }
-            }
-            _ => {}
-        }
-    }
-    sizes
-}
-
-fn part1(sizes: &HashMap<String, usize>) -> usize {
-    sizes.values().filter(|&&size| size < 100000).sum()
+fn part1(fs: &FileSystem) -> usize {
+    let sizes = fs.sizes();
+    fs.dirs().map(|dir| sizes[dir]).filter(|&size| size < 100000).sum()
 }
 
-fn part2(sizes: &HashMap<String, usize>) -> usize {
-    let avail = 70_000_000 - *sizes.get("/").unwrap();
-    *sizes.values().filter(|&&size| avail + size >= 30_000_000).min().unwrap()
+fn part2(fs: &FileSystem, capacity: usize, required: usize) -> usize {
+    cleanup::plan(fs, capacity, required, Strategy::Single).unwrap().freed
 }
 
 fn main() {
     let input = include_str!("input.txt");
-    let input = sizes(&input);
+    let (input, issues) = FileSystem::replay(input);
+    for issue in issues {
+        eprintln!("warning: {}", issue);
+    }
     println!("{}", part1(&input));
-    println!("{}", part2(&input));
+    println!("{}", part2(&input, CAPACITY, REQUIRED));
+
+    // day7 tree, day7 du, day7 clean [single|bytes|deletions] [capacity] [required],
+    // day7 export <dir>, day7 session <dir> or day7 find [-name GLOB] [-type d|f] [-minsize N] [-maxsize N] [-mindepth N] [-maxdepth N]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("tree") => print!("{}", query::tree(&input)),
+        Some("du") => {
+            for (path, size) in query::du(&input) {
+                println!("{}\t{}", query::human(size), path);
+            }
+        }
+        Some("find") => {
+            let mut find = Find::default();
+            for option in args[1..].chunks(2) {
+                let value = option.get(1).expect("missing value for find option");
+                match option[0].as_str() {
+                    "-name" => find.name = Some(value.clone()),
+                    "-type" if value == "d" => find.kind = Some(Type::Dir),
+                    "-type" if value == "f" => find.kind = Some(Type::File),
+                    "-minsize" => find.min_size = Some(value.parse().unwrap()),
+                    "-maxsize" => find.max_size = Some(value.parse().unwrap()),
+                    "-mindepth" => find.min_depth = Some(value.parse().unwrap()),
+                    "-maxdepth" => find.max_depth = Some(value.parse().unwrap()),
+                    other => panic!("Unknown find option {} {}", other, value),
+                }
+            }
+            for path in find.run(&input) {
+                println!("{}", path);
+            }
+        }
+        Some("clean") => {
+            let strategy = match args.get(1).map(String::as_str) {
+                None | Some("single") => Strategy::Single,
+                Some("bytes") => Strategy::FewestBytes,
+                Some("deletions") => Strategy::FewestDeletions,
+                Some(other) => panic!("Unknown strategy {}", other),
+            };
+            let capacity = args.get(2).map_or(CAPACITY, |n| n.parse().unwrap());
+            let required = args.get(3).map_or(REQUIRED, |n| n.parse().unwrap());
+            match cleanup::plan(&input, capacity, required, strategy) {
+                Some(plan) => {
+                    for dir in plan.dirs {
+                        println!("{}", input.path(dir));
+                    }
+                    println!("{} freed", plan.freed);
+                }
+                None => println!("Can't free {} on a {} disk", required, capacity),
+            }
+        }
+        Some("export") => disk::export(&input, Path::new(&args[1])).unwrap(),
+        Some("session") => print!("{}", disk::session(Path::new(&args[1])).unwrap()),
+        Some(other) => panic!("Unknown command {}", other),
+        None => {}
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const INPUT: &'static str = "$ cd /\n\
-    $ ls\n\
-    dir a\n\
-    14848514 b.txt\n\
-    8504156 c.dat\n\
-    dir d\n\
-    $ cd a\n\
-    $ ls\n\
-    dir e\n\
-    29116 f\n\
-    2557 g\n\
-    62596 h.lst\n\
-    $ cd e\n\
-    $ ls\n\
-    584 i\n\
-    $ cd ..\n\
-    $ cd ..\n\
-    $ cd d\n\
-    $ ls\n\
-    4060174 j\n\
-    8033020 d.log\n\
-    5626152 d.ext\n\
-    7214296 k";
+    const INPUT: &str = include_str!("test.txt");
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(&sizes(INPUT)), 95437);
+        assert_eq!(part1(&FileSystem::from(INPUT)), 95437);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(&sizes(INPUT)), 24933642);
+        assert_eq!(part2(&FileSystem::from(INPUT), CAPACITY, REQUIRED), 24933642);
     }
 }
@@ -0,0 +1,200 @@
+use crate::filesystem::{FileSystem, Kind, NodeId, ROOT};
+
+// Sizes the way du -h shows them: 1K is 1024, rounded up, one decimal below 10
+pub fn human(size: usize) -> String {
+    let units = ["", "K", "M", "G", "T", "P"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        size.to_string()
+    } else if (value * 10.0).ceil() < 100.0 {
+        format!("{:.1}{}", (value * 10.0).ceil() / 10.0, units[unit])
+    } else {
+        format!("{}{}", value.ceil(), units[unit])
+    }
+}
+
+// Like the puzzle's picture of the filesystem, with directory sizes filled in:
+// - / (dir, size=48381165)
+//   - a (dir, size=94853)
+//     - e (dir, size=584)
+//       - i (file, size=584)
+pub fn tree(fs: &FileSystem) -> String {
+    let sizes = fs.sizes();
+    let mut out = String::new();
+    let mut stack = vec![(ROOT, 0)];
+    while let Some((id, depth)) = stack.pop() {
+        let node = fs.node(id);
+        let name = if id == ROOT { "/" } else { node.name.as_str() };
+        let kind = match node.kind {
+            Kind::Dir(_) => "dir",
+            Kind::File(_) => "file",
+        };
+        out.push_str(&format!("{}- {} ({}, size={})\n", "  ".repeat(depth), name, kind, sizes[id]));
+        // Reversed so the first child comes off the stack first
+        let children: Vec<_> = fs.children(id).collect();
+        stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+    }
+    out
+}
+
+// Every directory with its total size, biggest first
+pub fn du(fs: &FileSystem) -> Vec<(String, usize)> {
+    let sizes = fs.sizes();
+    let mut dirs: Vec<_> = fs.dirs().map(|dir| (fs.path(dir), sizes[dir])).collect();
+    dirs.sort_by(|(a_path, a), (b_path, b)| b.cmp(a).then_with(|| a_path.cmp(b_path)));
+    dirs
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Dir,
+    File,
+}
+
+// Anything left as None matches everything. Directories are matched on their total size,
+// depth counts from the root at 0 like find's -mindepth and -maxdepth
+#[derive(Debug, Default)]
+pub struct Find {
+    pub name: Option<String>,
+    pub kind: Option<Type>,
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+}
+
+impl Find {
+    // Paths of everything that matches, in tree order
+    pub fn run(&self, fs: &FileSystem) -> Vec<String> {
+        let sizes = fs.sizes();
+        let mut found = Vec::new();
+        let mut stack = vec![(ROOT, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            if self.max_depth.is_none_or(|max| depth < max) {
+                let children: Vec<_> = fs.children(id).collect();
+                stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+            }
+            if self.matches(fs, id, sizes[id], depth) {
+                found.push(fs.path(id));
+            }
+        }
+        found
+    }
+
+    fn matches(&self, fs: &FileSystem, id: NodeId, size: usize, depth: usize) -> bool {
+        let kind = if fs.is_dir(id) { Type::Dir } else { Type::File };
+        let name = if id == ROOT { "/" } else { fs.node(id).name.as_str() };
+        self.name.as_ref().is_none_or(|pattern| glob(pattern, name))
+            && self.kind.is_none_or(|want| want == kind)
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self.min_depth.is_none_or(|min| depth >= min)
+            && self.max_depth.is_none_or(|max| depth <= max)
+    }
+}
+
+// Shell style name matching, * is any run of characters and ? is any one character
+pub fn glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // matched[j] is whether the pattern so far matches the first j characters of the name
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for p in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = include_str!("test.txt");
+
+    #[test]
+    fn test_tree() {
+        let fs = FileSystem::from(INPUT);
+        assert_eq!(tree(&fs), "\
+- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+");
+    }
+
+    #[test]
+    fn test_du() {
+        let fs = FileSystem::from(INPUT);
+        assert_eq!(du(&fs), vec![
+            ("/".to_string(), 48381165),
+            ("/d".to_string(), 24933642),
+            ("/a".to_string(), 94853),
+            ("/a/e".to_string(), 584),
+        ]);
+    }
+
+    #[test]
+    fn test_human() {
+        assert_eq!(human(584), "584");
+        assert_eq!(human(1024), "1.0K");
+        assert_eq!(human(94853), "93K");
+        assert_eq!(human(10 * 1024 - 1), "10K");
+        assert_eq!(human(48381165), "47M");
+        assert_eq!(human(8504156), "8.2M");
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("*.txt", "b.txt"));
+        assert!(glob("d.*", "d.log"));
+        assert!(glob("?", "k"));
+        assert!(glob("*", ""));
+        assert!(glob("h*l*t", "h.lst"));
+        assert!(!glob("*.txt", "b.txt.bak"));
+        assert!(!glob("?", "ab"));
+        assert!(!glob("d", "d.log"));
+    }
+
+    #[test]
+    fn test_find() {
+        let fs = FileSystem::from(INPUT);
+        let find = Find { name: Some("d*".to_string()), ..Default::default() };
+        assert_eq!(find.run(&fs), vec!["/d", "/d/d.ext", "/d/d.log"]);
+
+        let find = Find { kind: Some(Type::File), min_size: Some(5_000_000), max_size: Some(8_100_000), ..Default::default() };
+        assert_eq!(find.run(&fs), vec!["/d/d.ext", "/d/d.log", "/d/k"]);
+
+        let find = Find { kind: Some(Type::Dir), max_size: Some(100_000), ..Default::default() };
+        assert_eq!(find.run(&fs), vec!["/a", "/a/e"]);
+
+        let find = Find { min_depth: Some(2), max_depth: Some(2), ..Default::default() };
+        assert_eq!(find.run(&fs), vec!["/a/e", "/a/f", "/a/g", "/a/h.lst", "/d/d.ext", "/d/d.log", "/d/j", "/d/k"]);
+
+        let find = Find { max_depth: Some(0), ..Default::default() };
+        assert_eq!(find.run(&fs), vec!["/"]);
+    }
+}
@@ -0,0 +1,48 @@
+// Binary netpbm images, one pixel per tree
+
+// Visible trees in white, hidden ones in black
+pub fn pgm(visible: &[Vec<bool>]) -> Vec<u8> {
+    let height = visible.len();
+    let width = visible.first().map_or(0, |row| row.len());
+    let mut image = format!("P5\n{} {}\n255\n", width, height).into_bytes();
+    image.extend(visible.iter().flatten().map(|&v| if v { 255 } else { 0 }));
+    image
+}
+
+// Scores from black through red and yellow up to white for the best spot.
+// Scaled to the best score in the forest, so an all-zero forest stays black
+pub fn ppm(scores: &[Vec<usize>]) -> Vec<u8> {
+    let height = scores.len();
+    let width = scores.first().map_or(0, |row| row.len());
+    let max = scores.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for &score in scores.iter().flatten() {
+        let heat = score * 765 / max;
+        image.extend([heat.min(255), heat.clamp(255, 510) - 255, heat.max(510) - 510].map(|c| c as u8));
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pgm() {
+        let image = pgm(&[vec![true, true, true], vec![true, false, true]]);
+        assert_eq!(image, b"P5\n3 2\n255\n\xff\xff\xff\xff\x00\xff");
+    }
+
+    #[test]
+    fn test_ppm() {
+        let image = ppm(&[vec![0, 1, 2], vec![3, 4, 8]]);
+        let (header, pixels) = image.split_at(11);
+        assert_eq!(header, b"P6\n3 2\n255\n");
+        assert_eq!(pixels.chunks(3).collect::<Vec<_>>(), vec![
+            &[0, 0, 0], &[95, 0, 0], &[191, 0, 0],
+            &[255, 31, 0], &[255, 127, 0], &[255, 255, 255],
+        ]);
+        // Nothing to scale by
+        assert_eq!(&ppm(&[vec![0, 0]])[11..], &[0; 6]);
+    }
+}
@@ -1,71 +1,138 @@
-use itertools::Itertools;
+mod heatmap;
 
-fn parse(input: &str) -> Vec<Vec<u32>> {
-    input.lines().map(|line| {
-        line.chars().map(|c| c.to_digit(10).unwrap()).collect()
-    }).collect()
+use std::fmt;
+use std::fs;
+
+// The grid of tree heights, row by row. Every row is width trees long
+#[derive(Debug, PartialEq)]
+struct Forest {
+    width: usize,
+    height: usize,
+    trees: Vec<Vec<u32>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    NotADigit { line: usize, column: usize, found: char },
+    Ragged { line: usize, expected: usize, found: usize },
 }
 
-fn part1(input: &Vec<Vec<u32>>) -> usize {
-    // Need to check the rows and columns
-    // If all left of the current number are less than the current number add 1
-    // If all right of the current number are less than the current number add 1
-    // If all above the current number are less than the current number add 1
-    // If all below the current number are less than the current number add 1
-
-    // We can skip the outside edges because they will always be visible
-    let len = input.len();
-    (1..len-1)
-        .cartesian_product(1..len-1)
-        .map(|(y, x)| {
-            let height = input[y][x];
-            // Get all heights in the 4 directions around the current point
-            let col = input.iter().map(|row| row[x]).collect::<Vec<u32>>();
-            let (up, down) = col.split_at(y);
-            let (left, right) = input[y].split_at(x);
-            let up = up.iter().rev().all(|h| *h < height);
-            let left = left.iter().rev().all(|h| *h < height);
-            let right = right.iter().skip(1).all(|h| *h < height);
-            let down = down.iter().skip(1).all(|h| *h < height);
-            [up, down, left, right].iter().any(|b| *b)
-        })
-        .filter(|b| *b)
-        .count()
-        + (len - 1) * 4
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::NotADigit { line, column, found } => write!(f, "line {}, column {}: {:?} is not a height", line, column, found),
+            ParseError::Ragged { line, expected, found } => write!(f, "line {}: {} trees, the first row had {}", line, found, expected),
+        }
+    }
 }
 
-fn part2(input: &Vec<Vec<u32>>) -> usize {
-    // Need to find the distances to the nearest point that is greater or equal to the current point
-    let len = input.len();
-    (0..len)
-        .cartesian_product(0..len)
-        .map(|(y, x)| {
-            let height = input[y][x];
-            // Get all heights in the 4 directions around the current point
-            let col = input.iter().map(|row| row[x]).collect::<Vec<u32>>();
-            let (up, down) = col.split_at(y);
-            let (left, right) = input[y].split_at(x);
-            let up = up.iter().rev().collect::<Vec<&u32>>();
-            let left = left.iter().rev().collect::<Vec<&u32>>();
-            let right = right.iter().skip(1).collect::<Vec<&u32>>();
-            let down = down.iter().skip(1).collect::<Vec<&u32>>();
-            [up, down, left, right].iter().map(|hs| {
-                hs.iter().position(|h| **h >= height).map(|a| a + 1).unwrap_or(hs.len())
-            }).product()
-        })
-        .max().unwrap()
+fn parse(input: &str) -> Result<Forest, ParseError> {
+    let mut trees: Vec<Vec<u32>> = Vec::new();
+    for (idx, line) in input.lines().enumerate() {
+        let row = line.chars().enumerate().map(|(column, c)| {
+            c.to_digit(10).ok_or(ParseError::NotADigit { line: idx + 1, column: column + 1, found: c })
+        }).collect::<Result<Vec<_>, _>>()?;
+        if let Some(first) = trees.first() {
+            if row.len() != first.len() {
+                return Err(ParseError::Ragged { line: idx + 1, expected: first.len(), found: row.len() });
+            }
+        }
+        trees.push(row);
+    }
+    Ok(Forest { width: trees.first().map_or(0, |row| row.len()), height: trees.len(), trees })
+}
+
+// Visibility from outside and scenic score of every tree
+struct Views {
+    visible: Vec<Vec<bool>>,
+    scores: Vec<Vec<usize>>,
+}
+
+// Looks back along one line of trees from each tree in turn.
+// The stack holds the trees behind the current one that nothing since has been at least as tall as,
+// so whatever is left on top after popping the shorter ones is the first tree that blocks the view
+fn scan(forest: &Forest, line: impl Iterator<Item = (usize, usize)>, views: &mut Views) {
+    let mut stack: Vec<(usize, u32)> = Vec::new();
+    for (i, (y, x)) in line.enumerate() {
+        let height = forest.trees[y][x];
+        while stack.last().is_some_and(|&(_, h)| h < height) {
+            stack.pop();
+        }
+        match stack.last() {
+            Some(&(j, _)) => views.scores[y][x] *= i - j,
+            None => {
+                // Nothing in the way all the way to the edge
+                views.scores[y][x] *= i;
+                views.visible[y][x] = true;
+            }
+        }
+        stack.push((i, height));
+    }
+}
+
+fn views(forest: &Forest) -> Views {
+    let Forest { width, height, .. } = *forest;
+    let mut views = Views {
+        visible: vec![vec![false; width]; height],
+        scores: vec![vec![1; width]; height],
+    };
+    // Each tree gets looked at once per direction, so this is linear in the number of trees
+    for y in 0..height {
+        scan(forest, (0..width).map(|x| (y, x)), &mut views);
+        scan(forest, (0..width).rev().map(|x| (y, x)), &mut views);
+    }
+    for x in 0..width {
+        scan(forest, (0..height).map(|y| (y, x)), &mut views);
+        scan(forest, (0..height).rev().map(|y| (y, x)), &mut views);
+    }
+    views
+}
+
+fn part1(forest: &Forest) -> usize {
+    views(forest).visible.iter().flatten().filter(|&&v| v).count()
+}
+
+// Nothing to score in an empty forest
+fn part2(forest: &Forest) -> usize {
+    views(forest).scores.into_iter().flatten().max().unwrap_or(0)
+}
+
+// The k best treehouse spots as ((row, column), score), best first.
+// Ties go to whichever comes first reading the forest row by row
+fn top(forest: &Forest, k: usize) -> Vec<((usize, usize), usize)> {
+    let mut spots: Vec<_> = views(forest).scores.into_iter().enumerate().flat_map(|(y, row)| {
+        row.into_iter().enumerate().map(move |(x, score)| ((y, x), score))
+    }).collect();
+    spots.sort_by_key(|&(pos, score)| (std::cmp::Reverse(score), pos));
+    spots.truncate(k);
+    spots
 }
 
 fn main() {
     let input = include_str!("input.txt");
-    let parsed = parse(input);
-    println!("Part 1: {}", part1(&parsed));
-    println!("Part 2: {}", part2(&parsed));
+    let parsed = parse(input).unwrap_or_else(|err| panic!("{}", err));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|a| a.as_str()).collect::<Vec<_>>()[..] {
+        ["top", k] => {
+            for ((y, x), score) in top(&parsed, k.parse().unwrap()) {
+                println!("row {}, column {}: {}", y, x, score);
+            }
+        }
+        ["heatmap", visible, scores] => {
+            let views = views(&parsed);
+            fs::write(visible, heatmap::pgm(&views.visible)).unwrap();
+            fs::write(scores, heatmap::ppm(&views.scores)).unwrap();
+        }
+        _ => {
+            println!("Part 1: {}", part1(&parsed));
+            println!("Part 2: {}", part2(&parsed));
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    const INPUT: &'static str = "30373\n\
+    const INPUT: &str = "30373\n\
     25512\n\
     65332\n\
     33549\n\
@@ -73,8 +140,9 @@ mod test {
 
     #[test]
     fn it_parses() {
-        let parsed = super::parse(INPUT);
-        assert_eq!(parsed, vec![
+        let parsed = super::parse(INPUT).unwrap();
+        assert_eq!((parsed.width, parsed.height), (5, 5));
+        assert_eq!(parsed.trees, vec![
             vec![3, 0, 3, 7, 3],
             vec![2, 5, 5, 1, 2],
             vec![6, 5, 3, 3, 2],
@@ -85,13 +153,104 @@ mod test {
 
     #[test]
     fn it_solves_part1() {
-        let parsed = super::parse(INPUT);
+        let parsed = super::parse(INPUT).unwrap();
         assert_eq!(super::part1(&parsed), 21);
     }
 
     #[test]
     fn it_solves_part2() {
-        let parsed = super::parse(INPUT);
+        let parsed = super::parse(INPUT).unwrap();
         assert_eq!(super::part2(&parsed), 8);
     }
+
+    // The original scan in all four directions from every tree
+    fn brute_force(forest: &super::Forest) -> (usize, usize) {
+        let input = &forest.trees;
+        let mut visible = 0;
+        let mut best = 0;
+        for y in 0..forest.height {
+            for x in 0..forest.width {
+                let height = input[y][x];
+                let col = input.iter().map(|row| row[x]).collect::<Vec<u32>>();
+                let (up, down) = col.split_at(y);
+                let (left, right) = input[y].split_at(x);
+                let lines: [Vec<u32>; 4] = [
+                    up.iter().rev().copied().collect(),
+                    down.iter().skip(1).copied().collect(),
+                    left.iter().rev().copied().collect(),
+                    right.iter().skip(1).copied().collect(),
+                ];
+                if lines.iter().any(|hs| hs.iter().all(|h| *h < height)) {
+                    visible += 1;
+                }
+                let score: usize = lines.iter().map(|hs| {
+                    hs.iter().position(|h| *h >= height).map(|a| a + 1).unwrap_or(hs.len())
+                }).product();
+                best = best.max(score);
+            }
+        }
+        (visible, best)
+    }
+
+    #[test]
+    fn it_matches_brute_force() {
+        let parsed = super::parse(include_str!("input.txt")).unwrap();
+        assert_eq!((super::part1(&parsed), super::part2(&parsed)), brute_force(&parsed));
+    }
+
+    #[test]
+    fn it_scores_every_tree() {
+        let views = super::views(&super::parse(INPUT).unwrap());
+        assert_eq!(views.scores[1][2], 4);
+        assert_eq!(views.scores[3][2], 8);
+        assert_eq!(views.scores[0][0], 0);
+        assert!(views.visible[1][1]);
+        assert!(!views.visible[1][3]);
+    }
+
+    #[test]
+    fn it_handles_rectangles() {
+        // The example without its last two columns, and turned on its side
+        let tall = super::parse("303\n255\n653\n335\n353").unwrap();
+        assert_eq!((tall.width, tall.height), (3, 5));
+        assert_eq!(super::part1(&tall), 14);
+        assert_eq!(brute_force(&tall), (14, 2));
+        assert_eq!(super::part2(&tall), 2);
+        let wide = super::parse("32633\n05535\n35353").unwrap();
+        assert_eq!((wide.width, wide.height), (5, 3));
+        assert_eq!(super::part1(&wide), 14);
+        assert_eq!(super::part2(&wide), 2);
+
+        for forest in ["1234567\n7654321", "1\n9\n3\n5", "42", "3\n"] {
+            let forest = super::parse(forest).unwrap();
+            assert_eq!((super::part1(&forest), super::part2(&forest)), brute_force(&forest));
+        }
+        let interior = super::parse("11111111\n19191911\n11111111").unwrap();
+        assert_eq!(super::part1(&interior), 18 + 3);
+        assert_eq!(brute_force(&interior).0, 21);
+    }
+
+    #[test]
+    fn it_finds_the_best_spots() {
+        let parsed = super::parse(INPUT).unwrap();
+        assert_eq!(super::top(&parsed, 3), vec![((3, 2), 8), ((2, 1), 6), ((1, 2), 4)]);
+        assert_eq!(super::top(&parsed, 100).len(), 25);
+        assert_eq!(super::top(&parsed, 0), vec![]);
+    }
+
+    #[test]
+    fn it_handles_empty_forests() {
+        let empty = super::parse("").unwrap();
+        assert_eq!((empty.width, empty.height), (0, 0));
+        assert_eq!((super::part1(&empty), super::part2(&empty)), (0, 0));
+    }
+
+    #[test]
+    fn it_rejects_bad_rows() {
+        use super::ParseError;
+        assert_eq!(super::parse("303\n2551\n653"), Err(ParseError::Ragged { line: 2, expected: 3, found: 4 }));
+        assert_eq!(super::parse("303\n25\n653"), Err(ParseError::Ragged { line: 2, expected: 3, found: 2 }));
+        assert_eq!(super::parse("303\n2x5"), Err(ParseError::NotADigit { line: 2, column: 2, found: 'x' }));
+        assert_eq!(super::parse("303\n25").unwrap_err().to_string(), "line 2: 2 trees, the first row had 3");
+    }
 }
@@ -1,4 +1,8 @@
-use std::{collections::HashSet, ops::Add};
+mod render;
+mod script;
+
+use std::collections::HashSet;
+use std::fmt;
 use itertools::Itertools;
 
 type Point = (i32, i32);
@@ -8,7 +12,8 @@ pub fn for_each_window_mut<T, F>(slice: &mut [T], size: usize, mut function: F)
 where
     F: FnMut(&mut [T])
 {
-    for start in 0..=(slice.len().saturating_sub(size)) {
+    // No windows at all when the slice is shorter than one
+    for start in 0..(slice.len() + 1).saturating_sub(size) {
         function(&mut slice[start..][..size]);
     }
 }
@@ -18,6 +23,10 @@ enum Direction {
     Down(i32),
     Left(i32),
     Right(i32),
+    UpLeft(i32),
+    UpRight(i32),
+    DownLeft(i32),
+    DownRight(i32),
 }
 
 impl Direction {
@@ -27,6 +36,10 @@ impl Direction {
             Self::Down(_) => (0, 1),
             Self::Left(_) => (-1, 0),
             Self::Right(_) => (1, 0),
+            Self::UpLeft(_) => (-1, -1),
+            Self::UpRight(_) => (1, -1),
+            Self::DownLeft(_) => (-1, 1),
+            Self::DownRight(_) => (1, 1),
         }
     }
 
@@ -36,56 +49,148 @@ impl Direction {
             Self::Down(n) => n,
             Self::Left(n) => n,
             Self::Right(n) => n,
+            Self::UpLeft(n) => n,
+            Self::UpRight(n) => n,
+            Self::DownLeft(n) => n,
+            Self::DownRight(n) => n,
+        }
+    }
+
+    fn new(dir: &str, n: i32) -> Self {
+        match dir {
+            "U" => Direction::Up(n),
+            "D" => Direction::Down(n),
+            "L" => Direction::Left(n),
+            "R" => Direction::Right(n),
+            "UL" => Direction::UpLeft(n),
+            "UR" => Direction::UpRight(n),
+            "DL" => Direction::DownLeft(n),
+            "DR" => Direction::DownRight(n),
+            _ => panic!("Invalid direction"),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Up(_) => "U",
+            Self::Down(_) => "D",
+            Self::Left(_) => "L",
+            Self::Right(_) => "R",
+            Self::UpLeft(_) => "UL",
+            Self::UpRight(_) => "UR",
+            Self::DownLeft(_) => "DL",
+            Self::DownRight(_) => "DR",
+        };
+        write!(f, "{} {}", name, self.inner())
+    }
+}
+
+// How far apart two knots can be and still count as touching
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Adjacency {
+    // Diagonal neighbours touch, the puzzle's rule
+    Chebyshev,
+    // Only the four straight neighbours touch
+    Manhattan,
+}
+
+// How a knot closes the gap once it's no longer touching
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Catchup {
+    // Straight along a shared row or column, otherwise diagonally, the puzzle's rule
+    Diagonal,
+    // One axis at a time, whichever is further off first and x on a tie
+    Orthogonal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Follow {
+    adjacency: Adjacency,
+    catchup: Catchup,
+    // Knots can be this far apart before the one behind has to move
+    length: i32,
+}
+
+impl Default for Follow {
+    fn default() -> Self {
+        Self { adjacency: Adjacency::Chebyshev, catchup: Catchup::Diagonal, length: 1 }
+    }
+}
+
+impl Follow {
+    fn touching(&self, (dx, dy): Point) -> bool {
+        let distance = match self.adjacency {
+            Adjacency::Chebyshev => dx.abs().max(dy.abs()),
+            Adjacency::Manhattan => dx.abs() + dy.abs(),
+        };
+        distance <= self.length
+    }
+
+    // Where a knot moves to once the knot in front of it has moved.
+    // It keeps stepping until it's touching again, which is one step with the puzzle's rule
+    fn apply(&self, head: Point, tail: Point) -> Point {
+        let (x, y) = head;
+        let (mut tx, mut ty) = tail;
+        while !self.touching((x - tx, y - ty)) {
+            let (dx, dy) = (x - tx, y - ty);
+            match self.catchup {
+                // signum is 0 along a shared row or column, so this covers the straight moves too
+                Catchup::Diagonal => {
+                    tx += dx.signum();
+                    ty += dy.signum();
+                }
+                Catchup::Orthogonal if dx.abs() >= dy.abs() => tx += dx.signum(),
+                Catchup::Orthogonal => ty += dy.signum(),
+            }
         }
+        (tx, ty)
     }
 }
 
-struct State {
-    points: Points,
-    head: Point,
-    tail: Point,
+// knots[0] is the head, the last one is the tail
+struct Rope {
+    follow: Follow,
+    knots: Vec<Point>,
+    // Every point each knot has been at, indexed the same as knots
+    visited: Vec<Points>,
 }
 
-impl State {
-    fn new() -> Self {
-        let mut points = Points::new();
-        points.insert((0, 0));
+impl Rope {
+    fn new(len: usize) -> Self {
+        Self::with_follow(len, Follow::default())
+    }
+
+    fn with_follow(len: usize, follow: Follow) -> Self {
+        assert!(len > 0, "A rope needs at least a head");
+        assert!(follow.length > 0, "Knots need to be at least 1 apart");
         Self {
-            points,
-            head: (0, 0),
-            tail: (0, 0),
+            follow,
+            knots: vec![(0, 0); len],
+            visited: vec![Points::from([(0, 0)]); len],
+        }
+    }
+
+    // Moves the head one step and lets the rest of the rope catch up
+    fn step(&mut self, (dx, dy): Point) {
+        let (x, y) = self.knots[0];
+        self.knots[0] = (x + dx, y + dy);
+        let follow = self.follow;
+        for_each_window_mut(&mut self.knots, 2, |pair| pair[1] = follow.apply(pair[0], pair[1]));
+        for (visited, knot) in self.visited.iter_mut().zip(&self.knots) {
+            visited.insert(*knot);
         }
     }
 
     fn move_head(&mut self, direction: &Direction) {
-        let (dx, dy) = direction.point();
-    
         for _ in 0..direction.inner() {
-            let (x, y) = self.head;
-            self.head = (x + dx, y + dy);
-            self.update_tail();
+            self.step(direction.point());
         }
     }
 
-    fn update_tail(&mut self) {
-        // Tail must never be further than 1 away from the head
-        // Only move diagonally if the head is not in the same row or column
-        let (x, y) = self.head;
-        let (tx, ty) = self.tail;
-        // If we're adjacent including diagonally skip
-        if (x - tx).abs() <= 1 && (y - ty).abs() <= 1 {
-            return;
-        }
-        let (dx, dy) = if x == tx {
-            (0, (y - ty).signum())
-        } else if y == ty {
-            ((x - tx).signum(), 0)
-        } else {
-            ((x - tx).signum(), (y - ty).signum())
-        };
-        self.tail = (tx + dx, ty + dy);
-        // We record the points visited
-        self.points.insert(self.tail);
+    fn tail_visited(&self) -> &Points {
+        self.visited.last().unwrap()
     }
 }
 
@@ -94,72 +199,61 @@ fn parse(input: &str) -> Vec<Direction> {
         .lines()
         .map(|line| {
             if let Some((dir, n)) = line.split_whitespace().collect_tuple() {
-                let n = n.parse().unwrap();
-                match dir {
-                    "U" => Direction::Up(n),
-                    "D" => Direction::Down(n),
-                    "L" => Direction::Left(n),
-                    "R" => Direction::Right(n),
-                    _ => panic!("Invalid direction"),
-                }
+                Direction::new(dir, n.parse().unwrap())
             } else {
                 panic!("Invalid line");
             }
         }).collect()
 }
 
-fn part1(input: &str) -> i32 {
+fn simulate(input: &str, len: usize) -> Rope {
     parse(input)
     .iter()
-    .fold(State::new(), |mut state, direction| {
-        state.move_head(direction);
-        state
-    }).points.len() as i32
+    .fold(Rope::new(len), |mut rope, direction| {
+        rope.move_head(direction);
+        rope
+    })
+}
+
+fn part1(input: &str) -> i32 {
+    simulate(input, 2).tail_visited().len() as i32
 }
 
 fn part2(input: &str) -> i32 {
-    let (_, visited) = parse(input)
-    .iter()
-    .fold((vec![(0, 0); 10], Points::new()), |(mut points, mut visited), direction| {
-        let (dx, dy) = direction.point();
-        for _ in 0..direction.inner() {
-            // Update the head
-            let h = points.get_mut(0).unwrap();
-            let (ox, oy) = *h;
-            *h = (ox + dx, oy + dy);
-            for_each_window_mut(&mut points, 2, |ps| {
-                let (x, y) = *ps.get(0).unwrap();
-                let tail = ps.get_mut(1).unwrap();
-                let (tx, ty) = *tail;
-                // If we're adjacent including diagonally skip
-                if (x - tx).abs() <= 1 && (y - ty).abs() <= 1 {
-                    return;
-                }
-                let (dx, dy) = if x == tx {
-                    (0, (y - ty).signum())
-                } else if y == ty {
-                    ((x - tx).signum(), 0)
-                } else {
-                    ((x - tx).signum(), (y - ty).signum())
-                };
-                *tail = (tx + dx, ty + dy);
-            });
-            visited.insert(*points.get(9).unwrap());
-        }
-        (points, visited)
-    });
-    visited.len() as i32
+    simulate(input, 10).tail_visited().len() as i32
 }
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1: {}", part1(input));
-    println!("Part 2: {}", part2(input));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|a| a.as_str()).collect::<Vec<_>>()[..] {
+        ["animate", len, every] => {
+            let every = match every {
+                "step" => render::Every::Step,
+                "instruction" => render::Every::Instruction,
+                _ => panic!("Expected step or instruction"),
+            };
+            print!("{}", render::animate(&parse(input), len.parse().unwrap(), every));
+        }
+        ["script", path] => {
+            for (name, rope) in script::run(&std::fs::read_to_string(path).unwrap()) {
+                println!("{}: tail visited {} positions", name, rope.tail_visited().len());
+            }
+        }
+        ["visited", len] => {
+            let rope = simulate(input, len.parse().unwrap());
+            print!("{}", render::visited(rope.tail_visited(), render::Area::around(&rope)));
+        }
+        _ => {
+            println!("Part 1: {}", part1(input));
+            println!("Part 2: {}", part2(input));
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    const INPUT: &'static str = "R 4\n\
+    const INPUT: &str = "R 4\n\
     U 4\n\
     L 3\n\
     D 1\n\
@@ -168,7 +262,7 @@ mod test {
     L 5\n\
     R 2";
 
-    const INPUT2: &'static str = "R 5\n\
+    const INPUT2: &str = "R 5\n\
     U 8\n\
     L 8\n\
     D 3\n\
@@ -193,4 +287,59 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT2), 36);
     }
+
+    #[test]
+    fn test_every_knot_visited() {
+        let rope = simulate(INPUT, 10);
+        assert_eq!(rope.knots[0], (2, -2));
+        // Only the head gets anywhere in the small example, the tail never moves
+        assert_eq!(rope.tail_visited().len(), 1);
+        assert_eq!(rope.visited[1].len(), 13);
+        assert_eq!(rope.visited.len(), 10);
+        // The second knot of a long rope goes where the tail of a short one does
+        assert_eq!(simulate(INPUT2, 10).visited[1], simulate(INPUT2, 2).visited[1]);
+        assert_eq!(simulate(INPUT2, 10).visited[9].len(), 36);
+    }
+
+    #[test]
+    fn test_single_knot() {
+        let rope = simulate(INPUT, 1);
+        assert_eq!(rope.tail_visited(), &rope.visited[0]);
+        assert_eq!(rope.knots, vec![(2, -2)]);
+    }
+
+    #[test]
+    fn test_diagonal_moves() {
+        let rope = simulate("UR 2\nDL 1\nDR 3", 2);
+        assert_eq!(rope.knots, vec![(4, 2), (3, 1)]);
+        assert_eq!(rope.tail_visited(), &Points::from([(0, 0), (1, -1), (2, 0), (3, 1)]));
+        assert_eq!(Direction::new("UL", 3).to_string(), "UL 3");
+    }
+
+    #[test]
+    fn test_follow_rules() {
+        let puzzle = Follow::default();
+        assert_eq!(puzzle.apply((2, 1), (0, 0)), (1, 1));
+        assert_eq!(puzzle.apply((1, 1), (0, 0)), (0, 0));
+        let orthogonal = Follow { catchup: Catchup::Orthogonal, ..puzzle };
+        assert_eq!(orthogonal.apply((2, 1), (0, 0)), (1, 0));
+        assert_eq!(orthogonal.apply((1, 2), (0, 0)), (0, 1));
+        // A diagonal head move can leave it two steps behind
+        assert_eq!(orthogonal.apply((2, 2), (0, 0)), (1, 1));
+        let manhattan = Follow { adjacency: Adjacency::Manhattan, catchup: Catchup::Orthogonal, length: 1 };
+        assert_eq!(manhattan.apply((1, 1), (0, 0)), (1, 0));
+        assert_eq!(manhattan.apply((0, -1), (0, 0)), (0, 0));
+        let loose = Follow { length: 2, ..puzzle };
+        assert_eq!(loose.apply((2, 2), (0, 0)), (0, 0));
+        assert_eq!(loose.apply((3, 0), (0, 0)), (1, 0));
+        assert_eq!(loose.apply((3, 3), (0, 0)), (1, 1));
+    }
+
+    #[test]
+    fn test_loose_rope() {
+        let mut rope = Rope::with_follow(3, Follow { length: 2, ..Follow::default() });
+        rope.move_head(&Direction::new("R", 6));
+        assert_eq!(rope.knots, vec![(6, 0), (4, 0), (2, 0)]);
+        assert_eq!(rope.tail_visited().len(), 3);
+    }
 }
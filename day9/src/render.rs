@@ -0,0 +1,158 @@
+use std::fmt::Write;
+
+use crate::{Direction, Point, Points, Rope};
+
+// The cells a drawing covers, inclusive at both ends. y grows downwards, same as Up is (0, -1)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Area {
+    min: Point,
+    max: Point,
+}
+
+impl Area {
+    // The smallest area holding the start and every point given
+    pub fn of<'a>(points: impl IntoIterator<Item = &'a Point>) -> Self {
+        points.into_iter().fold(Area { min: (0, 0), max: (0, 0) }, |area, &(x, y)| Area {
+            min: (area.min.0.min(x), area.min.1.min(y)),
+            max: (area.max.0.max(x), area.max.1.max(y)),
+        })
+    }
+
+    // Everywhere any knot has been, which is everywhere any knot is
+    pub fn around(rope: &Rope) -> Self {
+        Area::of(rope.visited.iter().flatten())
+    }
+
+    fn draw(&self, cell: impl Fn(Point) -> char) -> String {
+        let mut out = String::new();
+        for y in self.min.1..=self.max.1 {
+            out.extend((self.min.0..=self.max.0).map(|x| cell((x, y))));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// H for the head, T for the tail of a two knot rope, otherwise numbered from the head like the puzzle does
+fn label(index: usize, len: usize) -> char {
+    match index {
+        0 => 'H',
+        _ if len == 2 => 'T',
+        _ => std::char::from_digit(index as u32, 36).unwrap_or('?'),
+    }
+}
+
+// Knots nearer the head cover the ones behind them, and any knot covers the start
+pub fn knots(rope: &Rope, area: Area) -> String {
+    let len = rope.knots.len();
+    area.draw(|point| match rope.knots.iter().position(|&knot| knot == point) {
+        Some(index) => label(index, len),
+        None if point == (0, 0) => 's',
+        None => '.',
+    })
+}
+
+pub fn visited(points: &Points, area: Area) -> String {
+    area.draw(|point| match point {
+        (0, 0) => 's',
+        _ if points.contains(&point) => '#',
+        _ => '.',
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Every {
+    Step,
+    Instruction,
+}
+
+// The whole motion the way the puzzle illustrates it, a frame for the initial state and then
+// every frame under a header for the instruction it belongs to
+pub fn animate(directions: &[Direction], len: usize, every: Every) -> String {
+    let mut rope = Rope::new(len);
+    for direction in directions {
+        rope.move_head(direction);
+    }
+    let area = Area::around(&rope);
+
+    let mut rope = Rope::new(len);
+    let mut out = format!("== Initial State ==\n\n{}\n", knots(&rope, area));
+    for direction in directions {
+        write!(out, "== {} ==\n\n", direction).unwrap();
+        match every {
+            Every::Step => {
+                for _ in 0..direction.inner() {
+                    rope.step(direction.point());
+                    writeln!(out, "{}", knots(&rope, area)).unwrap();
+                }
+            }
+            Every::Instruction => {
+                rope.move_head(direction);
+                writeln!(out, "{}", knots(&rope, area)).unwrap();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, simulate};
+
+    const INPUT: &str = "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2";
+
+    #[test]
+    fn test_area() {
+        let rope = simulate(INPUT, 2);
+        assert_eq!(Area::around(&rope), Area { min: (0, -4), max: (5, 0) });
+        assert_eq!(Area::of(&[(3, 2)]), Area { min: (0, 0), max: (3, 2) });
+    }
+
+    #[test]
+    fn test_knots() {
+        let rope = simulate(INPUT, 2);
+        assert_eq!(knots(&rope, Area::around(&rope)), "......\n......\n.TH...\n......\ns.....\n");
+        let rope = simulate(INPUT, 10);
+        assert_eq!(knots(&rope, Area::around(&rope)), "......\n......\n.1H3..\n.5....\n6.....\n");
+    }
+
+    #[test]
+    fn test_visited() {
+        let rope = simulate(INPUT, 2);
+        assert_eq!(visited(rope.tail_visited(), Area::around(&rope)), "..##..\n...##.\n.####.\n....#.\ns###..\n");
+    }
+
+    #[test]
+    fn test_animate() {
+        let directions = parse(INPUT);
+        let frames = animate(&directions, 2, Every::Step);
+        assert!(frames.starts_with("== Initial State ==\n\n......\n......\n......\n......\nH.....\n\n== R 4 ==\n\n......\n......\n......\n......\nTH....\n\n"));
+        // One frame per step, 24 steps in all
+        assert_eq!(frames.matches("H").count(), 1 + 24);
+        let frames = animate(&directions, 2, Every::Instruction);
+        assert_eq!(frames.matches("==\n").count(), 1 + 8);
+        assert!(frames.ends_with("== R 2 ==\n\n......\n......\n.TH...\n......\ns.....\n\n"));
+    }
+
+    #[test]
+    fn test_larger_example() {
+        let rope = simulate("R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20", 10);
+        let area = Area::around(&rope);
+        let blank = format!("{}\n", ".".repeat(26));
+        let start = format!("...........s{}\n", ".".repeat(14));
+        let mut expected: String = "H123456789".chars().map(|c| format!("{}{}\n", c, ".".repeat(25))).collect();
+        expected += &blank.repeat(5);
+        expected += &start;
+        expected += &blank.repeat(5);
+        assert_eq!(knots(&rope, area), expected);
+        assert!(visited(rope.tail_visited(), area).ends_with(concat!(
+            "....#......s.........#....\n",
+            ".....#..............#.....\n",
+            "......#............#......\n",
+            ".......#..........#.......\n",
+            "........#........#........\n",
+            ".........########.........\n",
+        )));
+    }
+}
@@ -0,0 +1,110 @@
+use itertools::Itertools;
+
+use crate::{Adjacency, Catchup, Direction, Follow, Rope};
+
+// Instructions for several ropes at once, one per line:
+//
+//   rope long knots=10
+//   rope loose knots=3 adjacency=manhattan catchup=orthogonal length=2
+//   UL 3
+//   loose: R 4
+//
+// A move without a name moves every rope declared so far, a named one just that rope.
+// Anything after a # is a comment and blank lines are skipped
+pub fn run(input: &str) -> Vec<(String, Rope)> {
+    let mut ropes: Vec<(String, Rope)> = Vec::new();
+    for line in input.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(declaration) = line.strip_prefix("rope ") {
+            let (name, rope) = declare(declaration);
+            if ropes.iter().any(|(other, _)| *other == name) {
+                panic!("Rope {} declared twice", name);
+            }
+            ropes.push((name, rope));
+            continue;
+        }
+        let (name, motion) = match line.split_once(':') {
+            Some((name, motion)) => (Some(name.trim()), motion),
+            None => (None, line),
+        };
+        let direction = match motion.split_whitespace().collect_tuple() {
+            Some((dir, n)) => Direction::new(dir, n.parse().unwrap()),
+            None => panic!("Invalid line"),
+        };
+        let mut moved = false;
+        for (_, rope) in ropes.iter_mut().filter(|(other, _)| name.is_none_or(|name| other == name)) {
+            rope.move_head(&direction);
+            moved = true;
+        }
+        if !moved {
+            panic!("No rope to move for {:?}", line);
+        }
+    }
+    ropes
+}
+
+fn declare(declaration: &str) -> (String, Rope) {
+    let mut words = declaration.split_whitespace();
+    let name = words.next().expect("Rope needs a name").to_string();
+    let mut knots = 2;
+    let mut follow = Follow::default();
+    for setting in words {
+        match setting.split_once('=') {
+            Some(("knots", n)) => knots = n.parse().unwrap(),
+            Some(("length", n)) => follow.length = n.parse().unwrap(),
+            Some(("adjacency", "chebyshev")) => follow.adjacency = Adjacency::Chebyshev,
+            Some(("adjacency", "manhattan")) => follow.adjacency = Adjacency::Manhattan,
+            Some(("catchup", "diagonal")) => follow.catchup = Catchup::Diagonal,
+            Some(("catchup", "orthogonal")) => follow.catchup = Catchup::Orthogonal,
+            _ => panic!("Invalid setting {}", setting),
+        }
+    }
+    (name, Rope::with_follow(knots, follow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate;
+
+    #[test]
+    fn test_declarations() {
+        let ropes = run("rope a\nrope b knots=10 adjacency=manhattan catchup=orthogonal length=3 # comment");
+        assert_eq!(ropes[0].0, "a");
+        assert_eq!(ropes[0].1.knots.len(), 2);
+        assert_eq!(ropes[0].1.follow, Follow::default());
+        assert_eq!(ropes[1].1.knots.len(), 10);
+        assert_eq!(ropes[1].1.follow, Follow { adjacency: Adjacency::Manhattan, catchup: Catchup::Orthogonal, length: 3 });
+    }
+
+    #[test]
+    fn test_moves() {
+        let ropes = run("rope short\n\nrope long knots=10\nR 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2\nlong: DR 2\n");
+        // Same as the plain puzzle input for whatever every rope did
+        assert_eq!(ropes[0].1.tail_visited(), simulate("R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2", 2).tail_visited());
+        assert_eq!(ropes[0].1.knots[0], (2, -2));
+        assert_eq!(ropes[1].1.knots[0], (4, 0));
+    }
+
+    #[test]
+    fn test_late_declaration() {
+        let ropes = run("rope a\nR 3\nrope b\nR 3");
+        assert_eq!(ropes[0].1.knots[0], (6, 0));
+        assert_eq!(ropes[1].1.knots[0], (3, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "No rope to move")]
+    fn test_unknown_rope() {
+        run("rope a\nb: R 3");
+    }
+
+    #[test]
+    #[should_panic(expected = "declared twice")]
+    fn test_duplicate_rope() {
+        run("rope a\nrope a knots=3");
+    }
+}